@@ -1,13 +1,100 @@
+use std::time::Duration;
+
+use reqwest::header::RETRY_AFTER;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use serde_json::Value;
+
+use crate::errors::{ApiError, FieldError};
+use crate::transport::HttpResponse;
 
-#[derive(Deserialize)]
-/// Error response structure from sarufi api
+#[derive(Deserialize, Default)]
+/// Error response structure from sarufi api.
+///
+/// The api answers with `{"error": ...}`, `{"message": ...}` or FastAPI style
+/// `{"detail": ...}` bodies, every field is optional so a foreign body never hides the status.
 pub(crate) struct SarufiApiError {
-  error: String
+  #[serde(default)]
+  error: Option<Value>,
+  #[serde(default)]
+  message: Option<Value>,
+  #[serde(default)]
+  detail: Option<Value>,
 }
 
 impl SarufiApiError {
-    pub fn message(&self) -> String {
-      self.error.clone()
+    pub fn message(&self) -> Option<String> {
+      [&self.error, &self.message, &self.detail]
+        .into_iter()
+        .flatten()
+        .find_map(|value| match value {
+          Value::String(message) => Some(message.clone()),
+          Value::Array(_) => None,
+          other => Some(other.to_string()),
+        })
+    }
+
+    /// Field level problems of a `{"detail": [{"loc": [...], "msg": ..., "type": ...}]}` body
+    pub fn details(&self) -> Vec<FieldError> {
+      let items = match &self.detail {
+        Some(Value::Array(items)) => items,
+        _ => return Vec::new(),
+      };
+
+      items
+        .iter()
+        .map(|item| FieldError {
+          location: item["loc"]
+            .as_array()
+            .map(|loc| loc.iter().map(|l| l.as_str().map(str::to_owned).unwrap_or_else(|| l.to_string())).collect())
+            .unwrap_or_default(),
+          message: item["msg"].as_str().map(str::to_owned).unwrap_or_else(|| item.to_string()),
+          kind: item["type"].as_str().map(str::to_owned),
+        })
+        .collect()
     }
 }
+
+/// Maps an unsuccessful status and its raw body to the matching [`ApiError`] variant
+pub(crate) fn error_for_status(status: u16, retry_after: Option<Duration>, body: String) -> ApiError {
+  let parsed = serde_json::from_str::<SarufiApiError>(&body).unwrap_or_default();
+  let details = parsed.details();
+
+  let message = parsed
+    .message()
+    .or_else(|| details.first().map(|d| d.message.clone()))
+    .unwrap_or_else(|| {
+      let text = body.trim();
+      if text.is_empty() {
+        reqwest::StatusCode::from_u16(status)
+          .ok()
+          .and_then(|s| s.canonical_reason())
+          .unwrap_or("Unknown error")
+          .to_owned()
+      } else {
+        text.to_owned()
+      }
+    });
+
+  ApiError::from_status(status, message, details, retry_after, body)
+}
+
+/// Reads the `Retry-After` header, only the delay-seconds form is supported
+pub(crate) fn retry_after(response: &HttpResponse) -> Option<Duration> {
+  response
+    .header(RETRY_AFTER.as_str())?
+    .trim()
+    .parse::<u64>()
+    .ok()
+    .map(Duration::from_secs)
+}
+
+/// Turns an unsuccessful response into an [`ApiError`]
+pub(crate) fn error_from_response(response: HttpResponse) -> ApiError {
+  error_for_status(response.status, retry_after(&response), response.text())
+}
+
+/// Decodes a successful response body, keeping the raw body around on failure
+pub(crate) fn decode<T: DeserializeOwned>(response: HttpResponse) -> Result<T, ApiError> {
+  serde_json::from_slice(&response.body).map_err(|e| ApiError::Decode { message: format!("{}", e), body: response.text() })
+}
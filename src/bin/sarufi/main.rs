@@ -0,0 +1,434 @@
+//! `sarufi` command line tool to manage bots and talk to them.
+//!
+//! The api key is read from `--api-key`, the `SARUFI_API_KEY` environment
+//! variable or a `.env` file in the working directory.
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use sarufi::{ApiError, BotPatch, BotProject, BotSpec, Channel, FlowGraph, Sarufi, SnapshotStore};
+
+mod output;
+
+/// Where `bots snapshot` and `bots restore` keep versions unless told otherwise
+const DEFAULT_STORE: &str = ".sarufi/snapshots";
+
+use output::{Format, GraphFormat};
+
+#[derive(Parser)]
+#[command(name = "sarufi", version, about = "Manage and chat with Sarufi bots")]
+struct Cli {
+    /// Sarufi api key
+    #[arg(long, env = "SARUFI_API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+
+    /// Base url of the api, e.g. a staging deployment or a local mock
+    #[arg(long, env = "SARUFI_BASE_URL", global = true)]
+    base_url: Option<String>,
+
+    /// Output format
+    #[arg(long, short, value_enum, default_value = "table", global = true)]
+    output: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage bots
+    #[command(subcommand)]
+    Bots(BotsCommand),
+
+    /// Send a message to a bot
+    Chat {
+        bot_id: usize,
+        message: String,
+        /// Continue an existing chat, a new chat id is generated otherwise
+        #[arg(long)]
+        chat_id: Option<String>,
+        #[arg(long, default_value = "general")]
+        channel: String,
+        #[arg(long, default_value = "text")]
+        message_type: String,
+    },
+
+    /// Chat interactively with a bot, `/help` lists the commands
+    Repl {
+        bot_id: usize,
+        /// Continue an existing chat, a new chat id is generated otherwise
+        #[arg(long)]
+        chat_id: Option<String>,
+        #[arg(long, default_value = "general")]
+        channel: String,
+    },
+
+    /// Make the account match bot definitions kept in files, bots are matched by name
+    Apply {
+        /// JSON definitions, project directories or directories holding them
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Only print the plan
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete the bots missing from the definitions
+        #[arg(long)]
+        prune: bool,
+        /// Do not ask for confirmation before deleting bots
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Show the status of a chat
+    Status { bot_id: usize, chat_id: String },
+
+    /// Move a chat to another flow state
+    SetState { bot_id: usize, chat_id: String, next_state: String },
+}
+
+#[derive(Subcommand)]
+enum BotsCommand {
+    /// List all bots of the account
+    List,
+
+    /// Show a single bot
+    Get { id: usize },
+
+    /// Create a bot from a definition file, a project directory or from flags
+    Create {
+        /// JSON definition such as `src/data/test.json`, or a project directory
+        #[arg(long, conflicts_with = "name")]
+        file: Option<String>,
+        #[arg(long, required_unless_present = "file")]
+        name: Option<String>,
+        #[command(flatten)]
+        fields: BotFields,
+        /// Validate the definition locally before sending it
+        #[arg(long)]
+        validate: bool,
+    },
+
+    /// Update a bot, replacing it with a definition file or project directory, or changing only the given fields
+    Update {
+        id: usize,
+        /// JSON definition or project directory
+        #[arg(long, conflicts_with = "name")]
+        file: Option<String>,
+        #[arg(long)]
+        name: Option<String>,
+        #[command(flatten)]
+        fields: BotFields,
+        #[arg(long)]
+        validate: bool,
+    },
+
+    /// Show what differs between two bots or local definitions
+    Diff {
+        /// Id of a bot, a JSON definition or a project directory
+        source: String,
+        /// Id of the other bot, a JSON definition or a project directory
+        target: String,
+        /// Print a unified patch of the two definitions
+        #[arg(long)]
+        unified: bool,
+    },
+
+    /// Draw the flow of a bot or a local definition as a graph
+    Graph {
+        /// Id of the bot, a JSON definition or a project directory
+        target: String,
+        #[arg(long, value_enum, default_value = "dot")]
+        format: GraphFormat,
+    },
+
+    /// Write the definition of a bot to a JSON file that `create --file` reads back
+    Export { id: usize, path: String },
+
+    /// Save the current definition of a bot in the snapshot store
+    Snapshot {
+        id: usize,
+        #[arg(long, default_value = DEFAULT_STORE)]
+        store: String,
+    },
+
+    /// Roll a bot back to a snapshot, lists the snapshots when none is given
+    Restore {
+        id: usize,
+        snapshot: Option<String>,
+        #[arg(long, default_value = DEFAULT_STORE)]
+        store: String,
+        /// Do not ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Delete a bot
+    Delete {
+        id: usize,
+        /// Do not ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Args)]
+struct BotFields {
+    #[arg(long)]
+    description: Option<String>,
+    #[arg(long)]
+    industry: Option<String>,
+    #[arg(long)]
+    webhook_url: Option<String>,
+    /// Comma separated intents triggering the webhook
+    #[arg(long, value_delimiter = ',')]
+    webhook_trigger_intents: Option<Vec<String>>,
+    #[arg(long)]
+    visible_on_community: Option<bool>,
+    #[arg(long)]
+    language: Option<String>,
+}
+
+impl BotFields {
+    fn spec(&self, name: String) -> BotSpec {
+        BotSpec {
+            name,
+            description: self.description.clone(),
+            industry: self.industry.clone(),
+            webhook_url: self.webhook_url.clone(),
+            webhook_trigger_intents: self.webhook_trigger_intents.clone(),
+            visible_on_community: self.visible_on_community,
+            language: self.language.clone(),
+            ..BotSpec::default()
+        }
+    }
+
+    fn patch(&self, name: Option<String>) -> BotPatch {
+        BotPatch {
+            name,
+            description: self.description.clone(),
+            industry: self.industry.clone(),
+            webhook_url: self.webhook_url.clone(),
+            webhook_trigger_intents: self.webhook_trigger_intents.clone(),
+            visible_on_community: self.visible_on_community,
+            language: self.language.clone(),
+            ..BotPatch::default()
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn client(api_key: Option<&str>, base_url: Option<&str>) -> Result<Sarufi, ApiError> {
+    let api_key = api_key.ok_or(ApiError::InvalidApiKey())?;
+    let mut builder = Sarufi::builder(api_key).user_agent(concat!("sarufi-cli/", env!("CARGO_PKG_VERSION")));
+
+    if let Some(base_url) = base_url {
+        builder = builder.base_url(base_url);
+    }
+
+    builder.build()
+}
+
+/// A JSON definition file, or a project directory merged by [`BotProject`]
+fn read_spec(path: &str) -> Result<BotSpec, ApiError> {
+    if Path::new(path).is_dir() {
+        return Ok(BotProject::load(path)?.into_spec());
+    }
+    BotSpec::from_file(path)
+}
+
+/// Definitions found at `path`: a JSON file, a project directory, or a directory of those
+fn collect_specs(path: &Path, specs: &mut Vec<BotSpec>) -> Result<(), ApiError> {
+    if !path.is_dir() {
+        specs.push(BotSpec::from_file(path)?);
+        return Ok(());
+    }
+    if BotProject::is_project(path) {
+        specs.push(BotProject::load(path)?.into_spec());
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = std::fs::read_dir(path)?.map(|entry| entry.map(|e| e.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if BotProject::is_project(&entry) || entry.extension().is_some_and(|e| e == "json") {
+            collect_specs(&entry, specs)?;
+        }
+    }
+    Ok(())
+}
+
+/// Fails with the diagnostics of an invalid definition, warnings are printed and let through
+fn check(spec: &BotSpec) -> Result<(), ApiError> {
+    let diagnostics = spec.validate();
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    if sarufi::has_errors(&diagnostics) {
+        return Err(ApiError::InvalidSpec(diagnostics));
+    }
+    Ok(())
+}
+
+async fn run(cli: Cli) -> Result<(), ApiError> {
+    let Cli { api_key, base_url, output: format, command } = cli;
+    // built on demand, commands working on local files need no api key
+    let api = || client(api_key.as_deref(), base_url.as_deref());
+
+    match command {
+        Command::Bots(BotsCommand::List) => output::bots(&api()?.get_all_bots().await?, format),
+        Command::Bots(BotsCommand::Get { id }) => output::bot(&api()?.get_bot(id).await?, format),
+        Command::Bots(BotsCommand::Create { file, name, fields, validate }) => {
+            let spec = match (file, name) {
+                (Some(file), _) => read_spec(&file)?,
+                (None, Some(name)) => fields.spec(name),
+                (None, None) => return Err(ApiError::GenericError("either --file or --name is required".to_owned())),
+            };
+
+            if validate {
+                check(&spec)?;
+            }
+
+            output::bot(&api()?.create_bot(&spec).await?, format);
+        }
+        Command::Bots(BotsCommand::Update { id, file, name, fields, validate }) => {
+            let api = api()?;
+            let bot = match file {
+                Some(file) => {
+                    let spec = read_spec(&file)?;
+                    if validate {
+                        check(&spec)?;
+                    }
+                    api.update_bot(id, &spec).await?
+                }
+                None => {
+                    let patch = fields.patch(name);
+                    if validate {
+                        let mut spec = BotSpec::from(&api.get_bot(id).await?);
+                        patch.apply(&mut spec);
+                        check(&spec)?;
+                    }
+                    api.patch_bot(id, &patch).await?
+                }
+            };
+
+            output::bot(&bot, format);
+        }
+        Command::Bots(BotsCommand::Diff { source, target, unified }) => {
+            let mut specs = Vec::new();
+            for side in [&source, &target] {
+                specs.push(match side.parse::<usize>() {
+                    Ok(id) => BotSpec::from(&api()?.get_bot(id).await?),
+                    Err(_) => read_spec(side)?,
+                });
+            }
+            output::diff(&sarufi::diff_specs(&specs[0], &specs[1]), unified, format);
+        }
+        Command::Bots(BotsCommand::Graph { target, format }) => {
+            let graph = match target.parse::<usize>() {
+                Ok(id) => FlowGraph::from_bot(&api()?.get_bot(id).await?),
+                Err(_) => FlowGraph::from_spec(&read_spec(&target)?),
+            };
+            output::graph(&graph, format);
+        }
+        Command::Bots(BotsCommand::Export { id, path }) => {
+            api()?.export_bot(id, &path).await?;
+            eprintln!("exported bot {} to {}", id, path);
+        }
+        Command::Bots(BotsCommand::Snapshot { id, store }) => {
+            let snapshot = api()?.snapshot_bot(id, &SnapshotStore::open(store)?).await?;
+            eprintln!("saved snapshot {} to {}", snapshot.id, snapshot.path.display());
+        }
+        Command::Bots(BotsCommand::Restore { id, snapshot, store, yes }) => {
+            let store = SnapshotStore::open(store)?;
+            let Some(snapshot) = snapshot else {
+                for snapshot in store.list(id)? {
+                    println!("{}", snapshot.id);
+                }
+                return Ok(());
+            };
+
+            let snapshot = store.get(id, &snapshot)?;
+            if !yes && !confirm(&format!("Replace bot {} with snapshot {}?", id, snapshot.id)) {
+                return Err(ApiError::GenericError("aborted".to_owned()));
+            }
+            output::bot(&api()?.restore(id, &snapshot).await?, format);
+        }
+        Command::Bots(BotsCommand::Delete { id, yes }) => {
+            if !yes && !confirm(&format!("Delete bot {}?", id)) {
+                return Err(ApiError::GenericError("aborted".to_owned()));
+            }
+
+            api()?.delete_bot(id).await?;
+            eprintln!("deleted bot {}", id);
+        }
+        Command::Apply { paths, dry_run, prune, yes } => {
+            let mut desired = Vec::new();
+            for path in &paths {
+                collect_specs(Path::new(path), &mut desired)?;
+            }
+
+            let api = api()?;
+            let plan = api.plan(&desired, prune).await?;
+            output::plan(&plan, format);
+            if dry_run || plan.is_empty() {
+                return Ok(());
+            }
+
+            let (_, _, delete) = plan.counts();
+            if delete > 0 && !yes && !confirm(&format!("Delete {} bots?", delete)) {
+                return Err(ApiError::GenericError("aborted".to_owned()));
+            }
+
+            let bots = api.apply(&plan).await?;
+            eprintln!("applied, {} bots created or updated", bots.len());
+        }
+        Command::Chat { bot_id, message, chat_id, channel, message_type } => {
+            let api = api()?;
+            let chat_id = chat_id.unwrap_or_else(|| api.conversation(bot_id).chat_id().to_owned());
+            let response = api.respond(bot_id, &chat_id, &message, &message_type, Channel::from(channel)).await?;
+
+            eprintln!("chat id: {}", chat_id);
+            output::reply(&response, format);
+        }
+        Command::Repl { bot_id, chat_id, channel } => {
+            let api = api()?;
+            let mut conversation = api.conversation(bot_id).with_channel(channel);
+            if let Some(chat_id) = chat_id {
+                conversation = conversation.with_chat_id(chat_id);
+            }
+
+            let stdin = std::io::stdin();
+            sarufi::repl::run(&mut conversation, stdin.lock(), std::io::stdout()).await?;
+        }
+        Command::Status { bot_id, chat_id } => output::raw(&api()?.chat_status(bot_id, &chat_id).await?, format),
+        Command::SetState { bot_id, chat_id, next_state } => {
+            output::raw(&api()?.update_conversation_state(bot_id, &chat_id, &next_state).await?, format)
+        }
+    }
+
+    Ok(())
+}
+
+fn confirm(question: &str) -> bool {
+    use std::io::Write;
+
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush().ok();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "Y" | "yes")
+}
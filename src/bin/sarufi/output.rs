@@ -0,0 +1,150 @@
+use clap::ValueEnum;
+use sarufi::{Bot, BotDiff, ConversationResponse, FlowGraph, Plan};
+use serde::Serialize;
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// Graph languages `bots graph` renders to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+/// Prints a value as pretty JSON
+pub fn json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => println!("{}", text),
+        Err(e) => eprintln!("failed to render output: {}", e),
+    }
+}
+
+/// Renders rows under a header with columns padded to the widest cell
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    let mut out = vec![line(header.to_vec())];
+    out.extend(rows.iter().map(|row| line(row.iter().map(String::as_str).collect())));
+    out.join("\n")
+}
+
+pub fn bots(bots: &[Bot], format: Format) {
+    match format {
+        Format::Json => json(&bots),
+        Format::Table => {
+            let rows: Vec<Vec<String>> = bots
+                .iter()
+                .map(|bot| {
+                    vec![
+                        bot.id.to_string(),
+                        bot.name.clone(),
+                        bot.industry.clone(),
+                        bot.language.clone(),
+                        bot.intents.len().to_string(),
+                        bot.updated_at.clone(),
+                    ]
+                })
+                .collect();
+            println!("{}", table(&["ID", "NAME", "INDUSTRY", "LANGUAGE", "INTENTS", "UPDATED"], &rows));
+        }
+    }
+}
+
+pub fn bot(bot: &Bot, format: Format) {
+    match format {
+        Format::Json => json(bot),
+        Format::Table => {
+            let mut intents: Vec<&String> = bot.intents.keys().collect();
+            intents.sort();
+
+            let rows = vec![
+                vec!["id".to_owned(), bot.id.to_string()],
+                vec!["name".to_owned(), bot.name.clone()],
+                vec!["description".to_owned(), bot.description.clone()],
+                vec!["industry".to_owned(), bot.industry.clone()],
+                vec!["language".to_owned(), bot.language.clone()],
+                vec!["model".to_owned(), bot.model_name.clone()],
+                vec!["intents".to_owned(), intents.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")],
+                vec!["flow states".to_owned(), bot.flows.state_names().join(", ")],
+                vec!["webhook url".to_owned(), bot.webhook_url.clone()],
+                vec!["webhook intents".to_owned(), bot.webhook_trigger_intents.join(", ")],
+                vec!["visible".to_owned(), bot.visible_on_community.to_string()],
+                vec!["created".to_owned(), bot.created_at.clone()],
+                vec!["updated".to_owned(), bot.updated_at.clone()],
+            ];
+            println!("{}", table(&["FIELD", "VALUE"], &rows));
+        }
+    }
+}
+
+pub fn reply(response: &ConversationResponse, format: Format) {
+    match format {
+        Format::Json => json(&response.raw),
+        Format::Table => {
+            for line in sarufi::repl::render(response) {
+                println!("{}", line);
+            }
+            if let Some(state) = &response.next_state {
+                println!("(next state: {})", state);
+            }
+        }
+    }
+}
+
+/// Prints a raw api answer, pretty printed when it is JSON
+pub fn raw(text: &str, format: Format) {
+    match (serde_json::from_str::<serde_json::Value>(text), format) {
+        (Ok(value), Format::Json) => json(&value),
+        (Ok(serde_json::Value::Object(object)), Format::Table) => {
+            let rows: Vec<Vec<String>> = object
+                .iter()
+                .map(|(k, v)| vec![k.clone(), v.as_str().map(str::to_owned).unwrap_or_else(|| v.to_string())])
+                .collect();
+            println!("{}", table(&["FIELD", "VALUE"], &rows));
+        }
+        _ => println!("{}", text),
+    }
+}
+
+pub fn plan(plan: &Plan, format: Format) {
+    match format {
+        Format::Json => json(plan),
+        Format::Table => println!("{}", plan),
+    }
+}
+
+pub fn diff(diff: &BotDiff, unified: bool, format: Format) {
+    match format {
+        Format::Json => json(&diff.to_json()),
+        Format::Table if unified => print!("{}", diff.unified()),
+        Format::Table => println!("{}", diff),
+    }
+}
+
+pub fn graph(graph: &FlowGraph, format: GraphFormat) {
+    match format {
+        GraphFormat::Dot => println!("{}", graph.to_dot()),
+        GraphFormat::Mermaid => println!("{}", graph.to_mermaid()),
+    }
+}
@@ -0,0 +1,219 @@
+//! Synchronous client for code that does not run an async runtime.
+//!
+//! [`Sarufi`] wraps the async [`crate::Sarufi`] together with a private
+//! single-threaded Tokio runtime, every method blocks until the api answered.
+//! Like `reqwest::blocking`, it must not be used from within an async runtime.
+//!
+//! ```no_run
+//! let api = sarufi::blocking::Sarufi::new("my-api-key")?;
+//! let bots = api.get_all_bots()?;
+//! let reply = api.send_message(bots[0].id, "chat-1", "Hello")?;
+//! println!("{}", reply.text());
+//! # Ok::<(), sarufi::ApiError>(())
+//! ```
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{ApiError, Bot, BotPatch, BotSpec, Channel, ConversationResponse, Plan, ReconcileOptions, SarufiBuilder, Turn};
+
+/// Blocking counterpart of [`crate::Sarufi`], cloning shares the runtime and the transport
+#[derive(Clone)]
+pub struct Sarufi {
+    inner: crate::Sarufi,
+    runtime: Arc<Runtime>,
+}
+
+impl Sarufi {
+    /// Creates a client with the default configuration, fails if the api key is empty
+    pub fn new<S: Into<String>>(api_key: S) -> Result<Sarufi, ApiError> {
+        Sarufi::from_builder(SarufiBuilder::new(api_key))
+    }
+
+    /// Builds a client from a configured [`SarufiBuilder`]
+    pub fn from_builder(builder: SarufiBuilder) -> Result<Sarufi, ApiError> {
+        let inner = builder.build()?;
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Sarufi { inner, runtime: Arc::new(runtime) })
+    }
+
+    /// The async client doing the work
+    pub fn inner(&self) -> &crate::Sarufi {
+        &self.inner
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.inner.base_url()
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    pub fn get_bot(&self, id: usize) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.get_bot(id))
+    }
+
+    pub fn get_all_bots(&self) -> Result<Vec<Bot>, ApiError> {
+        self.block_on(self.inner.get_all_bots())
+    }
+
+    /// Creates a new bot from its definition
+    pub fn create_bot(&self, spec: &BotSpec) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.create_bot(spec))
+    }
+
+    /// Creates a new bot from a JSON definition file
+    #[cfg(feature = "fs-import")]
+    pub fn create_bot_from_file(&self, file_path: &str) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.create_bot_from_file(file_path))
+    }
+
+    /// Creates a new bot from a project directory, see [`crate::BotProject`]
+    #[cfg(feature = "fs-import")]
+    pub fn create_bot_from_dir<P: AsRef<std::path::Path>>(&self, dir: P) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.create_bot_from_dir(dir))
+    }
+
+    /// Replaces the definition of a bot, fields left unset in `spec` are not sent
+    pub fn update_bot(&self, id: usize, spec: &BotSpec) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.update_bot(id, spec))
+    }
+
+    /// Replaces the definition of a bot with a project directory
+    #[cfg(feature = "fs-import")]
+    pub fn update_bot_from_dir<P: AsRef<std::path::Path>>(&self, id: usize, dir: P) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.update_bot_from_dir(id, dir))
+    }
+
+    /// See [`crate::Sarufi::patch_bot`]
+    pub fn patch_bot(&self, id: usize, patch: &BotPatch) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.patch_bot(id, patch))
+    }
+
+    pub fn delete_bot(&self, id: usize) -> Result<(), ApiError> {
+        self.block_on(self.inner.delete_bot(id))
+    }
+
+    /// See [`crate::Sarufi::export_bot`]
+    #[cfg(feature = "fs-import")]
+    pub fn export_bot<P: AsRef<std::path::Path>>(&self, id: usize, path: P) -> Result<BotSpec, ApiError> {
+        self.block_on(self.inner.export_bot(id, path))
+    }
+
+    /// Saves the current definition of a bot in a snapshot store
+    #[cfg(feature = "fs-import")]
+    pub fn snapshot_bot(&self, id: usize, store: &crate::SnapshotStore) -> Result<crate::Snapshot, ApiError> {
+        self.block_on(self.inner.snapshot_bot(id, store))
+    }
+
+    /// Pushes a snapshot back to a bot
+    #[cfg(feature = "fs-import")]
+    pub fn restore(&self, id: usize, snapshot: &crate::Snapshot) -> Result<Bot, ApiError> {
+        self.block_on(self.inner.restore(id, snapshot))
+    }
+
+    /// See [`crate::Sarufi::plan`]
+    pub fn plan(&self, desired: &[BotSpec], prune: bool) -> Result<Plan, ApiError> {
+        self.block_on(self.inner.plan(desired, prune))
+    }
+
+    /// See [`crate::Sarufi::apply`]
+    pub fn apply(&self, plan: &Plan) -> Result<Vec<Bot>, ApiError> {
+        self.block_on(self.inner.apply(plan))
+    }
+
+    /// See [`crate::Sarufi::reconcile`]
+    pub fn reconcile(&self, desired: Vec<BotSpec>, options: ReconcileOptions) -> Result<Plan, ApiError> {
+        self.block_on(self.inner.reconcile(desired, options))
+    }
+
+    /// Sends a message to a bot and returns its full reply
+    pub fn respond<C: Into<Channel>>(&self, bot_id: usize, chat_id: &str, message: &str, message_type: &str, channel: C) -> Result<ConversationResponse, ApiError> {
+        self.block_on(self.inner.respond(bot_id, chat_id, message, message_type, channel))
+    }
+
+    /// Sends a text message on the general channel
+    pub fn send_message(&self, bot_id: usize, chat_id: &str, message: &str) -> Result<ConversationResponse, ApiError> {
+        self.block_on(self.inner.send_message(bot_id, chat_id, message))
+    }
+
+    /// Says "Hello" to a bot on a fresh chat, see [`crate::Sarufi::chat`]
+    pub fn chat(&self, bot_id: usize) -> Result<String, ApiError> {
+        self.block_on(self.inner.chat(bot_id))
+    }
+
+    pub fn chat_status(&self, bot_id: usize, chat_id: &str) -> Result<String, ApiError> {
+        self.block_on(self.inner.chat_status(bot_id, chat_id))
+    }
+
+    pub fn update_conversation_state(&self, bot_id: usize, chat_id: &str, next_state: &str) -> Result<String, ApiError> {
+        self.block_on(self.inner.update_conversation_state(bot_id, chat_id, next_state))
+    }
+
+    /// Starts a conversation with a bot, see [`Conversation`]
+    pub fn conversation(&self, bot_id: usize) -> Conversation<'_> {
+        Conversation { inner: self.inner.conversation(bot_id), runtime: &self.runtime }
+    }
+}
+
+/// Blocking counterpart of [`crate::Conversation`]
+pub struct Conversation<'a> {
+    inner: crate::Conversation<'a>,
+    runtime: &'a Runtime,
+}
+
+impl<'a> Conversation<'a> {
+    /// Continues an existing chat instead of generating a new chat id
+    pub fn with_chat_id<S: Into<String>>(self, chat_id: S) -> Conversation<'a> {
+        Conversation { inner: self.inner.with_chat_id(chat_id), runtime: self.runtime }
+    }
+
+    /// Talks on another channel
+    pub fn with_channel<C: Into<Channel>>(self, channel: C) -> Conversation<'a> {
+        Conversation { inner: self.inner.with_channel(channel), runtime: self.runtime }
+    }
+
+    pub fn bot_id(&self) -> usize {
+        self.inner.bot_id()
+    }
+
+    pub fn chat_id(&self) -> &str {
+        self.inner.chat_id()
+    }
+
+    pub fn channel(&self) -> &Channel {
+        self.inner.channel()
+    }
+
+    /// The state the bot moved to after the last reply or [`Conversation::set_state`]
+    pub fn state(&self) -> Option<&str> {
+        self.inner.state()
+    }
+
+    /// Every exchange of this conversation, oldest first
+    pub fn transcript(&self) -> &[Turn] {
+        self.inner.transcript()
+    }
+
+    /// Sends a text message and records the exchange
+    pub fn send(&mut self, text: &str) -> Result<ConversationResponse, ApiError> {
+        self.runtime.block_on(self.inner.send(text))
+    }
+
+    /// Status of this chat as reported by the api
+    pub fn status(&self) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.status())
+    }
+
+    /// Moves the conversation to another flow state
+    pub fn set_state(&mut self, next_state: &str) -> Result<String, ApiError> {
+        self.runtime.block_on(self.inner.set_state(next_state))
+    }
+
+    /// Starts over with a new chat id and an empty transcript
+    pub fn reset(&mut self) {
+        self.inner.reset()
+    }
+}
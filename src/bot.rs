@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::flow::Flow;
+
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Bot{
@@ -10,7 +12,7 @@ pub struct Bot{
     pub name: String,
     pub description: String,
     pub intents: HashMap<String, Vec<String>>,
-    pub flows: HashMap<String, serde_json::Value>,
+    pub flows: Flow,
     pub model_name: String,
     pub evaluation_metrics: Option<EvaluationMetrics>,
     pub industry: String,
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, ClientBuilder, Proxy, Url};
+
+use crate::cassette::{Cassette, CassetteTransport};
+use crate::errors::ApiError;
+use crate::retry::RetryPolicy;
+use crate::transport::{ReqwestTransport, Transport};
+use crate::utils;
+use crate::Sarufi;
+
+/// Configures and builds a [`Sarufi`] client.
+///
+/// Every endpoint method on the resulting client is resolved against the
+/// configured base url, so the same code can talk to production, staging,
+/// a self-hosted gateway or a local mock server.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use sarufi::Sarufi;
+///
+/// let api = Sarufi::builder("my-api-key")
+///     .base_url("http://localhost:8080")
+///     .timeout(Duration::from_secs(10))
+///     .header("X-Request-Source", "ops")
+///     .build()
+///     .unwrap();
+/// ```
+pub struct SarufiBuilder {
+    api_key: String,
+    base_url: String,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    proxy: Option<Proxy>,
+    headers: Vec<(String, String)>,
+    default_headers: HeaderMap,
+    client: Option<Client>,
+    transport: Option<Arc<dyn Transport>>,
+    retry: RetryPolicy,
+    validate_bots: bool,
+    cassette: Option<Cassette>,
+}
+
+impl SarufiBuilder {
+    /// Starts a new builder for the given api key
+    pub fn new<S: Into<String>>(api_key: S) -> SarufiBuilder {
+        SarufiBuilder {
+            api_key: api_key.into(),
+            base_url: utils::BASE_URL.to_owned(),
+            timeout: None,
+            connect_timeout: None,
+            user_agent: None,
+            proxy: None,
+            headers: Vec::new(),
+            default_headers: HeaderMap::new(),
+            client: None,
+            transport: None,
+            retry: RetryPolicy::default(),
+            validate_bots: false,
+            cassette: None,
+        }
+    }
+
+    /// Starts a builder from the `SARUFI_API_KEY` and optional `SARUFI_BASE_URL`
+    /// environment variables, a `.env` file in the working directory is loaded first
+    #[cfg(feature = "env-config")]
+    pub fn from_env() -> Result<SarufiBuilder, ApiError> {
+        dotenv::dotenv().ok();
+
+        let api_key = std::env::var(utils::API_KEY_ENV).map_err(|_| ApiError::InvalidApiKey())?;
+        let mut builder = SarufiBuilder::new(api_key);
+        if let Some(base_url) = std::env::var(utils::BASE_URL_ENV).ok().filter(|url| !url.trim().is_empty()) {
+            builder = builder.base_url(base_url);
+        }
+        Ok(builder)
+    }
+
+    /// Sets the url every endpoint path is appended to, defaults to `https://developers.sarufi.io`
+    pub fn base_url<S: Into<String>>(mut self, base_url: S) -> SarufiBuilder {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Total timeout applied to each request
+    pub fn timeout(mut self, timeout: Duration) -> SarufiBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for establishing a connection
+    pub fn connect_timeout(mut self, timeout: Duration) -> SarufiBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Overrides the `User-Agent` header sent with each request
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> SarufiBuilder {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Routes all traffic through the given proxy
+    pub fn proxy(mut self, proxy: Proxy) -> SarufiBuilder {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Adds an extra header sent with every request, invalid names or values are reported by [`SarufiBuilder::build`]
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> SarufiBuilder {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Adds a set of extra headers sent with every request
+    pub fn default_headers(mut self, headers: HeaderMap) -> SarufiBuilder {
+        self.default_headers.extend(headers);
+        self
+    }
+
+    /// Uses a preconfigured `reqwest::Client`.
+    ///
+    /// Timeouts, user agent and proxy set on this builder are ignored in that case,
+    /// they have to be configured on the client itself.
+    pub fn client(mut self, client: Client) -> SarufiBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets how transient failures are retried, see [`RetryPolicy`]
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> SarufiBuilder {
+        self.retry = retry;
+        self
+    }
+
+    /// Sends every request exactly once
+    pub fn no_retries(self) -> SarufiBuilder {
+        self.retry_policy(RetryPolicy::disabled())
+    }
+
+    /// Validates bot definitions before creating or updating them,
+    /// definitions with errors are rejected with [`ApiError::InvalidSpec`] without calling the api
+    pub fn validate_bots(mut self, validate: bool) -> SarufiBuilder {
+        self.validate_bots = validate;
+        self
+    }
+
+    /// Sends requests through a custom [`Transport`] instead of reqwest,
+    /// the timeouts, user agent, proxy and client settings are then ignored
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> SarufiBuilder {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Records the traffic to a [`Cassette`], or answers every request from one when it replays
+    pub fn cassette(mut self, cassette: Cassette) -> SarufiBuilder {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Builds the client, failing on an empty api key, a malformed base url or invalid headers 🤒
+    pub fn build(self) -> Result<Sarufi, ApiError> {
+        utils::validate_keys(&self.api_key)?;
+
+        let base_url = self.base_url.trim_end_matches('/').to_owned();
+        Url::parse(&base_url)
+            .map_err(|e| ApiError::GenericError(format!("Invalid base url {:?}: {}", base_url, e)))?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, header_value(&format!("Bearer {}", self.api_key))?);
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.extend(self.default_headers);
+
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ApiError::GenericError(format!("Invalid header name {:?}: {}", name, e)))?;
+            headers.insert(name, header_value(value)?);
+        }
+
+        let mut transport: Arc<dyn Transport> = match (self.transport, self.client) {
+            (Some(transport), _) => transport,
+            (None, Some(client)) => Arc::new(ReqwestTransport::new(client)),
+            (None, None) => {
+                let mut builder = ClientBuilder::new();
+
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+
+                if let Some(user_agent) = self.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+
+                Arc::new(ReqwestTransport::new(builder.build()?))
+            }
+        };
+
+        let cassette = self.cassette.map(Arc::new);
+        if let Some(cassette) = &cassette {
+            transport = Arc::new(CassetteTransport { cassette: cassette.clone(), inner: transport });
+        }
+
+        Ok(Sarufi { transport, base_url, headers, retry: self.retry, validate_bots: self.validate_bots, cassette })
+    }
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, ApiError> {
+    value
+        .parse()
+        .map_err(|e| ApiError::GenericError(format!("Invalid header value: {}", e)))
+}
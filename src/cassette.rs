@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::ApiError;
+use crate::transport::{HttpRequest, HttpResponse, Transport, TransportError, TransportErrorKind};
+
+/// Response headers kept in a recording, the rest is noise for the client
+const RECORDED_HEADERS: [&str; 2] = ["content-type", "retry-after"];
+
+/// Request/response pairs stored as JSON lines, to record real traffic once and
+/// replay it deterministically afterwards.
+///
+/// A replaying client never touches the network: each request is answered with the
+/// first unused interaction matching its method, path and body. Identical requests
+/// get their recorded responses in order, so retries replay as they happened.
+///
+/// ```no_run
+/// use sarufi::{Cassette, Sarufi};
+///
+/// # async fn run() -> Result<(), sarufi::ApiError> {
+/// // once, against the real api
+/// let api = Sarufi::builder("my-api-key").cassette(Cassette::record("tests/cassettes/bots.jsonl")?).build()?;
+/// api.get_all_bots().await?;
+///
+/// // from then on, offline
+/// let api = Sarufi::builder("unused").cassette(Cassette::replay("tests/cassettes/bots.jsonl")?).build()?;
+/// let bots = api.get_all_bots().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// One recorded exchange, a line of the cassette file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// The parts of a request interactions are matched on, credentials are never recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl Cassette {
+    /// Records every exchange to `path`, replacing an existing file
+    pub fn record<P: AsRef<Path>>(path: P) -> Result<Cassette, ApiError> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(&path)?;
+
+        Ok(Cassette { path, mode: Mode::Record, interactions: Mutex::new(Vec::new()) })
+    }
+
+    /// Serves the exchanges recorded in `path`
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Cassette, ApiError> {
+        let path = path.as_ref().to_path_buf();
+        let mut interactions = Vec::new();
+
+        for line in BufReader::new(File::open(&path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                interactions.push((serde_json::from_str(&line)?, false));
+            }
+        }
+
+        Ok(Cassette { path, mode: Mode::Replay, interactions: Mutex::new(interactions) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.mode == Mode::Record
+    }
+
+    /// Interactions recorded or loaded so far
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.lock().iter().map(|(interaction, _)| interaction.clone()).collect()
+    }
+
+    /// Recorded interactions no request was matched with yet
+    pub fn unused(&self) -> Vec<Interaction> {
+        self.lock().iter().filter(|(_, used)| !used).map(|(interaction, _)| interaction.clone()).collect()
+    }
+
+    /// Answers a request from the recording
+    fn play(&self, request: &HttpRequest) -> Result<HttpResponse, TransportError> {
+        let recorded = RecordedRequest::from(request);
+        let mut interactions = self.lock();
+
+        let (interaction, used) = interactions
+            .iter_mut()
+            .find(|(interaction, used)| !*used && interaction.request == recorded)
+            .ok_or_else(|| {
+                let message = format!("{} has no recorded response for {} {}", self.path.display(), request.method, request.path);
+                TransportError::new(TransportErrorKind::Other, message)
+            })?;
+        *used = true;
+
+        Ok(interaction.response.to_response())
+    }
+
+    /// Appends an exchange to the recording
+    fn store(&self, request: &HttpRequest, response: &HttpResponse) -> Result<(), TransportError> {
+        let headers = RECORDED_HEADERS
+            .iter()
+            .filter_map(|&name| response.header(name).map(|value| (name.to_owned(), value.to_owned())))
+            .collect();
+        let interaction = Interaction {
+            request: RecordedRequest::from(request),
+            response: RecordedResponse { status: response.status, headers, body: response.text() },
+        };
+
+        let write = |interaction: &Interaction| -> std::io::Result<()> {
+            let mut file = OpenOptions::new().append(true).open(&self.path)?;
+            writeln!(file, "{}", serde_json::to_string(interaction)?)
+        };
+
+        let mut interactions = self.lock();
+        write(&interaction).map_err(|e| {
+            TransportError::new(TransportErrorKind::Other, format!("failed to record to {}: {}", self.path.display(), e))
+        })?;
+        interactions.push((interaction, true));
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<(Interaction, bool)>> {
+        self.interactions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl From<&HttpRequest> for RecordedRequest {
+    fn from(request: &HttpRequest) -> RecordedRequest {
+        RecordedRequest { method: request.method.to_string(), path: request.path.clone(), body: request.json() }
+    }
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> HttpResponse {
+        let headers = self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?)))
+            .collect();
+
+        HttpResponse { status: self.status, headers, body: self.body.clone().into_bytes() }
+    }
+}
+
+/// Routes requests through a cassette, the inner transport only sees traffic being recorded
+pub(crate) struct CassetteTransport {
+    pub(crate) cassette: Arc<Cassette>,
+    pub(crate) inner: Arc<dyn Transport>,
+}
+
+#[async_trait]
+impl Transport for CassetteTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        if !self.cassette.is_recording() {
+            return self.cassette.play(&request);
+        }
+
+        let response = self.inner.send(request.clone()).await?;
+        self.cassette.store(&request, &response)?;
+        Ok(response)
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::whatsapp::Action;
+
+/// Channel a conversation happens on, it selects the endpoint and the shape of the replies
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Channel {
+    #[default]
+    General,
+    /// Replies come back as typed WhatsApp [`Action`]s
+    WhatsApp,
+    Telegram,
+    /// Any other channel name known to the api
+    Other(String),
+}
+
+impl Channel {
+    /// Name of the channel as expected by the api
+    pub fn as_str(&self) -> &str {
+        match self {
+            Channel::General => "general",
+            Channel::WhatsApp => "whatsapp",
+            Channel::Telegram => "telegram",
+            Channel::Other(name) => name,
+        }
+    }
+
+    /// Conversation endpoint serving this channel
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            Channel::WhatsApp => "/conversation/whatsapp",
+            _ => "/conversation",
+        }
+    }
+}
+
+impl From<&str> for Channel {
+    fn from(name: &str) -> Channel {
+        match name.to_lowercase().as_str() {
+            "general" => Channel::General,
+            "whatsapp" => Channel::WhatsApp,
+            "telegram" => Channel::Telegram,
+            _ => Channel::Other(name.to_owned()),
+        }
+    }
+}
+
+impl From<String> for Channel {
+    fn from(name: String) -> Channel {
+        Channel::from(name.as_str())
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Channel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Channel, D::Error> {
+        Ok(Channel::from(String::deserialize(deserializer)?))
+    }
+}
+
+/// Reply of the conversation endpoint.
+///
+/// All messages of the reply are kept in order, nested message groups are flattened.
+/// WhatsApp replies are parsed into `actions`, their texts and media are mirrored in
+/// `messages` so every channel can be rendered the same way. `raw` holds the full
+/// decoded body so fields not modeled here are never lost.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversationResponse {
+    pub messages: Vec<Message>,
+    pub next_state: Option<String>,
+    pub memory: Map<String, Value>,
+    pub actions: Vec<Action>,
+    pub raw: Value,
+}
+
+/// A single message sent back by the bot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Message {
+    /// Plain text
+    Text(String),
+    /// Images, videos, audios, documents or stickers sent through a `send_*` block
+    Media { kind: MediaKind, items: Vec<Media> },
+    /// Any other structured payload
+    Other(Value),
+}
+
+/// Kind of media attached to a [`Message::Media`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Sticker,
+}
+
+/// A media item, usually `{"link": ..., "caption": ...}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Media {
+    pub link: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+}
+
+impl MediaKind {
+    /// Maps the `send_*` key used by flows and responses to a media kind
+    pub fn from_key(key: &str) -> Option<MediaKind> {
+        match key {
+            "send_images" | "send_image" => Some(MediaKind::Image),
+            "send_videos" | "send_video" => Some(MediaKind::Video),
+            "send_audios" | "send_audio" => Some(MediaKind::Audio),
+            "send_documents" | "send_document" => Some(MediaKind::Document),
+            "send_stickers" | "send_sticker" => Some(MediaKind::Sticker),
+            _ => None,
+        }
+    }
+}
+
+impl Message {
+    /// Parses one message entry, arrays are flattened into several messages
+    pub fn parse_all(value: &Value) -> Vec<Message> {
+        let mut messages = Vec::new();
+        collect_messages(value, &mut messages);
+        messages
+    }
+
+    fn from_action(action: &Action) -> Vec<Message> {
+        match action {
+            Action::SendMessage(texts) => texts.iter().cloned().map(Message::Text).collect(),
+            Action::SendMedia { kind, items } => vec![Message::Media { kind: *kind, items: items.clone() }],
+            Action::SendReplyButton(_) | Action::SendButton(_) | Action::Other(_) => Vec::new(),
+        }
+    }
+
+    /// The text of a [`Message::Text`]
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Message::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+fn collect_messages(value: &Value, messages: &mut Vec<Message>) {
+    match value {
+        Value::Null => {}
+        Value::String(text) => messages.push(Message::Text(text.clone())),
+        Value::Array(items) => items.iter().for_each(|item| collect_messages(item, messages)),
+        Value::Object(object) => messages.push(parse_media(object).unwrap_or_else(|| Message::Other(value.clone()))),
+        other => messages.push(Message::Other(other.clone())),
+    }
+}
+
+fn parse_media(object: &Map<String, Value>) -> Option<Message> {
+    if object.len() != 1 {
+        return None;
+    }
+
+    let (key, items) = object.iter().next()?;
+    let kind = MediaKind::from_key(key)?;
+    let items = match items {
+        Value::Array(_) => serde_json::from_value(items.clone()).ok()?,
+        single => vec![serde_json::from_value(single.clone()).ok()?],
+    };
+
+    Some(Message::Media { kind, items })
+}
+
+impl ConversationResponse {
+    /// Builds a response from the decoded body, never fails so unexpected shapes stay available in `raw`
+    pub fn from_value(raw: Value) -> ConversationResponse {
+        let actions = Action::parse_all(&raw["actions"]);
+        let mut messages = Message::parse_all(&raw["message"]);
+        if messages.is_empty() {
+            messages = actions.iter().flat_map(Message::from_action).collect();
+        }
+        let next_state = raw["next_state"].as_str().map(str::to_owned);
+        let memory = raw["memory"].as_object().cloned().unwrap_or_default();
+
+        ConversationResponse { messages, next_state, memory, actions, raw }
+    }
+
+    /// All text messages joined by new lines
+    pub fn text(&self) -> String {
+        self.messages.iter().filter_map(Message::as_text).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Whether the bot reached the terminal `end` state
+    pub fn is_end(&self) -> bool {
+        self.next_state.as_deref() == Some("end")
+    }
+}
+
+impl From<Value> for ConversationResponse {
+    fn from(raw: Value) -> ConversationResponse {
+        ConversationResponse::from_value(raw)
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::bot::Bot;
+use crate::flow::FlowState;
+use crate::spec::BotSpec;
+
+/// Lines of context around each hunk of [`BotDiff::unified`]
+const CONTEXT: usize = 3;
+
+/// Structural differences between two definitions of a bot.
+///
+/// Intent examples and webhook trigger intents are compared as sets, flow states
+/// are compared message by message and transition by transition. The diff renders
+/// as grouped text with `Display`, as JSON with [`BotDiff::to_json`] and as a
+/// unified patch of the two definitions with [`BotDiff::unified`].
+///
+/// ```
+/// use sarufi::{diff_specs, BotSpec, FlowState};
+///
+/// let old = BotSpec::new("Pizza bot").intent("greetings", ["hi"]);
+/// let new = old.clone().intent("greetings", ["hi", "hello"]).state("greetings", FlowState::new(["Hello!"]).next("end"));
+///
+/// let diff = diff_specs(&old, &new);
+/// assert_eq!(diff.changes.len(), 2);
+/// println!("{}", diff);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BotDiff {
+    pub changes: Vec<Change>,
+    #[serde(skip)]
+    old_name: String,
+    #[serde(skip)]
+    new_name: String,
+    #[serde(skip)]
+    old_document: Value,
+    #[serde(skip)]
+    new_document: Value,
+}
+
+/// A single difference, `path` points at the changed part, e.g. `flow.order.next_state`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub section: Section,
+    pub kind: ChangeKind,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+/// Part of the bot a [`Change`] belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    /// Name, description, industry, language, model and visibility
+    Metadata,
+    Webhook,
+    Intents,
+    Flow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Differences between two live bots
+pub fn diff(old: &Bot, new: &Bot) -> BotDiff {
+    diff_specs(&BotSpec::from(old), &BotSpec::from(new))
+}
+
+/// What updating `bot` with `spec` would change, fields left unset in `spec` are not compared
+pub fn diff_spec(bot: &Bot, spec: &BotSpec) -> BotDiff {
+    diff_specs(&BotSpec::from(bot), spec)
+}
+
+/// Differences between two definitions, fields left unset in `new` are kept from `old`
+pub fn diff_specs(old: &BotSpec, new: &BotSpec) -> BotDiff {
+    let new = overlay(old, new);
+    let mut changes = Vec::new();
+
+    let mut value = |section, path: &str, old: Value, new: Value| {
+        if old != new {
+            changes.push(Change { section, kind: ChangeKind::Changed, path: path.to_owned(), old: Some(old), new: Some(new) });
+        }
+    };
+    value(Section::Metadata, "name", json(&old.name), json(&new.name));
+    value(Section::Metadata, "description", json(&old.description), json(&new.description));
+    value(Section::Metadata, "industry", json(&old.industry), json(&new.industry));
+    value(Section::Metadata, "language", json(&old.language), json(&new.language));
+    value(Section::Metadata, "model_name", json(&old.model_name), json(&new.model_name));
+    value(Section::Metadata, "visible_on_community", json(&old.visible_on_community), json(&new.visible_on_community));
+    value(Section::Metadata, "confidence_threshold", json(&old.confidence_threshold), json(&new.confidence_threshold));
+    value(Section::Webhook, "webhook_url", json(&old.webhook_url), json(&new.webhook_url));
+
+    let empty = Vec::new();
+    set(
+        &mut changes,
+        Section::Webhook,
+        "webhook_trigger_intents",
+        old.webhook_trigger_intents.as_ref().unwrap_or(&empty),
+        new.webhook_trigger_intents.as_ref().unwrap_or(&empty),
+    );
+
+    let (old_intents, new_intents) = (old.intents.clone().unwrap_or_default(), new.intents.clone().unwrap_or_default());
+    let names: BTreeSet<&String> = old_intents.keys().chain(new_intents.keys()).collect();
+    for name in names {
+        let path = format!("intents.{}", name);
+        match (old_intents.get(name), new_intents.get(name)) {
+            (Some(examples), None) => changes.push(Change::removed(Section::Intents, path, json(&sorted(examples)))),
+            (None, Some(examples)) => changes.push(Change::added(Section::Intents, path, json(&sorted(examples)))),
+            (Some(old), Some(new)) => set(&mut changes, Section::Intents, &format!("{}.examples", path), old, new),
+            (None, None) => {}
+        }
+    }
+
+    let (old_flow, new_flow) = (old.flow.clone().unwrap_or_default(), new.flow.clone().unwrap_or_default());
+    let states: BTreeSet<&str> = old_flow.state_names().into_iter().chain(new_flow.state_names()).collect();
+    for name in states {
+        let path = format!("flow.{}", name);
+        match (old_flow.get(name), new_flow.get(name)) {
+            (Some(state), None) => changes.push(Change::removed(Section::Flow, path, json(state))),
+            (None, Some(state)) => changes.push(Change::added(Section::Flow, path, json(state))),
+            (Some(old), Some(new)) => state_changes(&mut changes, &path, old, new),
+            (None, None) => {}
+        }
+    }
+
+    BotDiff {
+        changes,
+        old_name: old.name.clone(),
+        new_name: new.name.clone(),
+        old_document: document(old),
+        new_document: document(&new),
+    }
+}
+
+impl Change {
+    fn added(section: Section, path: String, new: Value) -> Change {
+        Change { section, kind: ChangeKind::Added, path, old: None, new: Some(new) }
+    }
+
+    fn removed(section: Section, path: String, old: Value) -> Change {
+        Change { section, kind: ChangeKind::Removed, path, old: Some(old), new: None }
+    }
+}
+
+impl BotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Changes of one section, in order
+    pub fn section(&self, section: Section) -> impl Iterator<Item = &Change> {
+        self.changes.iter().filter(move |change| change.section == section)
+    }
+
+    /// The changes as a JSON array
+    pub fn to_json(&self) -> Value {
+        json(&self.changes)
+    }
+
+    /// Unified patch between the two definitions written as pretty JSON, empty when nothing changed
+    pub fn unified(&self) -> String {
+        let old = pretty(&self.old_document);
+        let new = pretty(&self.new_document);
+        let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+
+        let ops = line_diff(&old, &new);
+        if ops.iter().all(|op| matches!(op, Op::Equal(..))) {
+            return String::new();
+        }
+
+        let mut out = format!("--- a/{}\n+++ b/{}\n", self.old_name, self.new_name);
+        for hunk in hunks(&ops) {
+            let old_start = ops[..hunk.start].iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+            let new_start = ops[..hunk.start].iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+            let old_len = ops[hunk.clone()].iter().filter(|op| !matches!(op, Op::Insert(_))).count();
+            let new_len = ops[hunk.clone()].iter().filter(|op| !matches!(op, Op::Delete(_))).count();
+            out.push_str(&format!("@@ -{} +{} @@\n", range(old_start, old_len), range(new_start, new_len)));
+
+            for op in &ops[hunk] {
+                let line = match op {
+                    Op::Equal(i) => format!(" {}", old[*i]),
+                    Op::Delete(i) => format!("-{}", old[*i]),
+                    Op::Insert(j) => format!("+{}", new[*j]),
+                };
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Section::Metadata => "metadata",
+            Section::Webhook => "webhook",
+            Section::Intents => "intents",
+            Section::Flow => "flow",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| value.as_ref().map(Value::to_string).unwrap_or_default();
+        match self.kind {
+            ChangeKind::Added => write!(f, "+ {}: {}", self.path, show(&self.new)),
+            ChangeKind::Removed => write!(f, "- {}: {}", self.path, show(&self.old)),
+            ChangeKind::Changed => write!(f, "~ {}: {} -> {}", self.path, show(&self.old), show(&self.new)),
+        }
+    }
+}
+
+/// One line per change grouped under its section
+impl fmt::Display for BotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+
+        let sections: BTreeSet<Section> = self.changes.iter().map(|change| change.section).collect();
+        let mut lines = Vec::new();
+        for section in sections {
+            lines.push(section.to_string());
+            lines.extend(self.section(section).map(|change| format!("  {}", change)));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// `old` with every field set in `new` replaced
+fn overlay(old: &BotSpec, new: &BotSpec) -> BotSpec {
+    let mut extra = old.extra.clone();
+    extra.extend(new.extra.clone());
+
+    BotSpec {
+        name: new.name.clone(),
+        description: new.description.clone().or_else(|| old.description.clone()),
+        industry: new.industry.clone().or_else(|| old.industry.clone()),
+        flow: new.flow.clone().or_else(|| old.flow.clone()),
+        intents: new.intents.clone().or_else(|| old.intents.clone()),
+        webhook_url: new.webhook_url.clone().or_else(|| old.webhook_url.clone()),
+        webhook_trigger_intents: new.webhook_trigger_intents.clone().or_else(|| old.webhook_trigger_intents.clone()),
+        visible_on_community: new.visible_on_community.or(old.visible_on_community),
+        language: new.language.clone().or_else(|| old.language.clone()),
+        confidence_threshold: new.confidence_threshold.or(old.confidence_threshold),
+        model_name: new.model_name.clone().or_else(|| old.model_name.clone()),
+        extra,
+    }
+}
+
+/// Items added to and removed from a list compared as a set
+fn set(changes: &mut Vec<Change>, section: Section, path: &str, old: &[String], new: &[String]) {
+    let (old, new): (BTreeSet<&String>, BTreeSet<&String>) = (old.iter().collect(), new.iter().collect());
+    changes.extend(new.difference(&old).map(|item| Change::added(section, path.to_owned(), json(item))));
+    changes.extend(old.difference(&new).map(|item| Change::removed(section, path.to_owned(), json(item))));
+}
+
+/// Messages and transitions of a state present on both sides
+fn state_changes(changes: &mut Vec<Change>, path: &str, old: &FlowState, new: &FlowState) {
+    let mut value = |key: &str, old: Value, new: Value| {
+        if old != new {
+            changes.push(Change { section: Section::Flow, kind: ChangeKind::Changed, path: format!("{}.{}", path, key), old: Some(old), new: Some(new) });
+        }
+    };
+    value("message", json(&old.message), json(&new.message));
+    value("next_state", json(&old.next_state), json(&new.next_state));
+    value("fallback_message", json(&old.fallback_message), json(&new.fallback_message));
+
+    let options: BTreeSet<&String> = old.choices.keys().chain(new.choices.keys()).collect();
+    for option in options {
+        let choice = format!("{}.choices.{}", path, option);
+        match (old.choices.get(option), new.choices.get(option)) {
+            (Some(target), None) => changes.push(Change::removed(Section::Flow, choice, json(target))),
+            (None, Some(target)) => changes.push(Change::added(Section::Flow, choice, json(target))),
+            (Some(a), Some(b)) if a != b => {
+                changes.push(Change { section: Section::Flow, kind: ChangeKind::Changed, path: choice, old: Some(json(a)), new: Some(json(b)) })
+            }
+            _ => {}
+        }
+    }
+
+    let keys: BTreeSet<&String> = old.extra.keys().chain(new.extra.keys()).collect();
+    for key in keys {
+        let extra = format!("{}.{}", path, key);
+        match (old.extra.get(key), new.extra.get(key)) {
+            (Some(value), None) => changes.push(Change::removed(Section::Flow, extra, value.clone())),
+            (None, Some(value)) => changes.push(Change::added(Section::Flow, extra, value.clone())),
+            (Some(a), Some(b)) if a != b => {
+                changes.push(Change { section: Section::Flow, kind: ChangeKind::Changed, path: extra, old: Some(a.clone()), new: Some(b.clone()) })
+            }
+            _ => {}
+        }
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn sorted(items: &[String]) -> Vec<&String> {
+    let mut items: Vec<&String> = items.iter().collect();
+    items.sort();
+    items
+}
+
+/// The definition with sorted keys and set-like lists sorted, so equal bots print the same
+fn document(spec: &BotSpec) -> Value {
+    let mut spec = spec.clone();
+    if let Some(intents) = &mut spec.intents {
+        intents.values_mut().for_each(|examples| examples.sort());
+    }
+    if let Some(intents) = &mut spec.webhook_trigger_intents {
+        intents.sort();
+    }
+    sort_keys(json(&spec))
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(String, Value)> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().map(|(key, value)| (key, sort_keys(value))).collect::<Map<_, _>>())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+/// A line of the edit script: kept or deleted (old index), or inserted (new index)
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Cells of the largest LCS table built, longer changed blocks are replaced as a whole
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Shortest edit script between two texts from their longest common subsequence.
+///
+/// Lines shared at the start and end are matched first, only the block between them
+/// goes through the quadratic LCS table, and a block too large for it is printed as
+/// deleted and inserted in full.
+fn line_diff(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    let (n, m) = (old_end - prefix, new_end - prefix);
+
+    let mut ops: Vec<Op> = (0..prefix).map(Op::Equal).collect();
+    if (n + 1).saturating_mul(m + 1) > MAX_LCS_CELLS {
+        ops.extend((prefix..old_end).map(Op::Delete));
+        ops.extend((prefix..new_end).map(Op::Insert));
+    } else {
+        let (old_mid, new_mid) = (&old[prefix..old_end], &new[prefix..new_end]);
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                ops.push(Op::Equal(prefix + i));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(Op::Delete(prefix + i));
+                i += 1;
+            } else {
+                ops.push(Op::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    ops.extend((old_end..old.len()).map(Op::Equal));
+    ops
+}
+
+/// Ranges of the edit script to print, changes with `CONTEXT` kept lines around them
+fn hunks(ops: &[Op]) -> Vec<std::ops::Range<usize>> {
+    let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+    for (index, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Equal(..)) {
+            continue;
+        }
+        let range = index.saturating_sub(CONTEXT)..(index + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => hunks.push(range),
+        }
+    }
+    hunks
+}
+
+/// `start,length` of a hunk side, lines counted from 1 and an empty side pointing before its line
+fn range(start: usize, length: usize) -> String {
+    if length == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, length)
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{json, Map, Value};
+
+use crate::bot::Bot;
+use crate::conversation::ConversationResponse;
+use crate::errors::ApiError;
+use crate::flow::{Flow, FlowState, MessageBlock, END_STATE};
+use crate::spec::BotSpec;
+
+/// Reply of a conversation when no intent matches the message
+pub const FALLBACK_MESSAGE: &str = "Sorry, I did not understand that";
+
+/// Runs a bot definition locally, to prototype and test flows without the api.
+///
+/// Messages are matched against the intent examples, equal first and then contained
+/// in the message, ignoring case and trailing punctuation. The state named after the
+/// matched intent answers, and the chat moves to its `next_state`. While a chat sits
+/// in a state other than `end`, the next message is the answer to that state: it is
+/// stored in the chat memory under the state name, and a choice state only lets
+/// through one of its options, repeating its fallback message otherwise.
+///
+/// Replies have the structure of the conversation endpoint, so assertions written
+/// against the engine hold for the live bot.
+///
+/// ```
+/// use sarufi::{BotSpec, FlowEngine, FlowState};
+///
+/// let spec = BotSpec::new("Pizza bot")
+///     .intent("order_pizza", ["I want a pizza"])
+///     .state("order_pizza", FlowState::new(["Which size? 1. Small 2. Large"]).choice("1", "small").fallback(["Please pick 1 or 2"]))
+///     .state("small", FlowState::new(["Small it is"]).next("end"));
+///
+/// let mut engine = FlowEngine::from_spec(&spec);
+/// assert_eq!(engine.send("chat-1", "I want a pizza!").next_state.as_deref(), Some("order_pizza"));
+/// assert_eq!(engine.send("chat-1", "3").text(), "Please pick 1 or 2");
+/// assert!(engine.send("chat-1", "1").is_end());
+/// assert_eq!(engine.memory("chat-1").unwrap()["order_pizza"], "1");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowEngine {
+    intents: BTreeMap<String, Vec<String>>,
+    flow: Flow,
+    fallback: Vec<MessageBlock>,
+    chats: HashMap<String, Chat>,
+}
+
+/// Where a chat stands in the flow
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Chat {
+    pub(crate) state: Option<String>,
+    pub(crate) memory: Map<String, Value>,
+}
+
+impl FlowEngine {
+    pub fn new(intents: HashMap<String, Vec<String>>, flow: Flow) -> FlowEngine {
+        FlowEngine {
+            intents: intents.into_iter().collect(),
+            flow,
+            fallback: vec![MessageBlock::Text(FALLBACK_MESSAGE.to_owned())],
+            chats: HashMap::new(),
+        }
+    }
+
+    pub fn from_bot(bot: &Bot) -> FlowEngine {
+        FlowEngine::new(bot.intents.clone(), bot.flows.clone())
+    }
+
+    pub fn from_spec(spec: &BotSpec) -> FlowEngine {
+        FlowEngine::new(spec.intents.clone().unwrap_or_default(), spec.flow.clone().unwrap_or_default())
+    }
+
+    /// Reply when no intent matches, `Sorry, I did not understand that` by default
+    pub fn fallback<I, S>(mut self, messages: I) -> FlowEngine
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fallback = messages.into_iter().map(|m| MessageBlock::Text(m.into())).collect();
+        self
+    }
+
+    /// Answers a message like the general conversation endpoint
+    pub fn send(&mut self, chat_id: &str, message: &str) -> ConversationResponse {
+        let mut chat = self.chats.remove(chat_id).unwrap_or_default();
+        let raw = self.reply(&mut chat, message, false);
+        self.chats.insert(chat_id.to_owned(), chat);
+        ConversationResponse::from_value(raw)
+    }
+
+    /// Answers a message like the WhatsApp conversation endpoint, texts come as `send_message` actions
+    pub fn send_whatsapp(&mut self, chat_id: &str, message: &str) -> ConversationResponse {
+        let mut chat = self.chats.remove(chat_id).unwrap_or_default();
+        let raw = self.reply(&mut chat, message, true);
+        self.chats.insert(chat_id.to_owned(), chat);
+        ConversationResponse::from_value(raw)
+    }
+
+    /// State a chat will answer from next, `None` for unknown chats
+    pub fn state(&self, chat_id: &str) -> Option<&str> {
+        self.chats.get(chat_id).and_then(|chat| chat.state.as_deref())
+    }
+
+    /// Answers collected in a chat, keyed by the state that asked
+    pub fn memory(&self, chat_id: &str) -> Option<&Map<String, Value>> {
+        self.chats.get(chat_id).map(|chat| &chat.memory)
+    }
+
+    /// Moves a chat to a state of the flow or to `end`
+    pub fn set_state(&mut self, chat_id: &str, next_state: &str) -> Result<(), ApiError> {
+        if next_state != END_STATE && !self.flow.contains_key(next_state) {
+            return Err(ApiError::GenericError(format!("state {:?} does not exist", next_state)));
+        }
+        self.chats.entry(chat_id.to_owned()).or_default().state = Some(next_state.to_owned());
+        Ok(())
+    }
+
+    /// Forgets the state and memory of a chat
+    pub fn reset(&mut self, chat_id: &str) {
+        self.chats.remove(chat_id);
+    }
+
+    /// Intent a message is recognized as
+    pub fn classify(&self, message: &str) -> Option<&str> {
+        let message = normalize(message);
+        let examples = || self.intents.iter().flat_map(|(name, examples)| examples.iter().map(move |e| (name, normalize(e))));
+
+        examples()
+            .find(|(_, example)| *example == message)
+            .or_else(|| examples().find(|(_, example)| example.len() >= 3 && message.contains(example.as_str())))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Advances a chat by one message, returns the raw reply body
+    pub(crate) fn reply(&self, chat: &mut Chat, message: &str, whatsapp: bool) -> Value {
+        let flow = &self.flow;
+        let pending = chat.state.clone().filter(|state| state != END_STATE && flow.contains_key(state));
+        let entered = match pending {
+            Some(pending) => {
+                chat.memory.insert(pending.clone(), message.into());
+                let state = &flow[&pending];
+                if state.is_choice() {
+                    match choose(state, message) {
+                        Some(target) if flow.contains_key(target) => Some(target.to_owned()),
+                        Some(END_STATE) => {
+                            chat.state = Some(END_STATE.to_owned());
+                            return answer(&[], END_STATE, &chat.memory, whatsapp);
+                        }
+                        Some(_) => None,
+                        None => {
+                            let fallback = if state.fallback_message.is_empty() { &state.message } else { &state.fallback_message };
+                            return answer(fallback, &pending, &chat.memory, whatsapp);
+                        }
+                    }
+                } else {
+                    Some(pending)
+                }
+            }
+            None => self.classify(message).filter(|intent| flow.contains_key(*intent)).map(str::to_owned),
+        };
+
+        let Some(name) = entered else {
+            chat.state = Some(END_STATE.to_owned());
+            return answer(&self.fallback, END_STATE, &chat.memory, whatsapp);
+        };
+
+        let state = &flow[&name];
+        let next_state = match &state.next_state {
+            Some(next_state) => next_state.clone(),
+            None if state.is_choice() => name.clone(),
+            None => END_STATE.to_owned(),
+        };
+        chat.state = Some(next_state.clone());
+
+        answer(&state.message, &next_state, &chat.memory, whatsapp)
+    }
+}
+
+/// Target of the choice matching an answer
+fn choose<'a>(state: &'a FlowState, message: &str) -> Option<&'a str> {
+    let message = normalize(message);
+    state.choices.iter().find(|(option, _)| normalize(option) == message).map(|(_, target)| target.as_str())
+}
+
+fn normalize(text: &str) -> String {
+    text.trim().trim_end_matches(['?', '!', '.']).trim().to_lowercase()
+}
+
+/// Reply body of a conversation endpoint, WhatsApp replies carry actions instead of messages
+fn answer(messages: &[MessageBlock], next_state: &str, memory: &Map<String, Value>, whatsapp: bool) -> Value {
+    let messages = serde_json::to_value(messages).unwrap_or_default();
+    if !whatsapp {
+        return json!({ "message": messages, "next_state": next_state, "memory": memory });
+    }
+
+    let mut actions = Vec::new();
+    let mut texts = Vec::new();
+    for block in messages.as_array().into_iter().flatten() {
+        match block {
+            Value::Object(_) => {
+                if !texts.is_empty() {
+                    actions.push(json!({ "send_message": std::mem::take(&mut texts) }));
+                }
+                actions.push(block.clone());
+            }
+            Value::Array(items) => texts.extend(items.iter().filter_map(Value::as_str).map(Value::from)),
+            Value::String(text) => texts.push(text.clone().into()),
+            _ => {}
+        }
+    }
+    if !texts.is_empty() {
+        actions.push(json!({ "send_message": texts }));
+    }
+
+    json!({ "actions": actions, "next_state": next_state, "memory": memory })
+}
@@ -1,27 +1,149 @@
-use failure::Fail;
-pub use failure::Error;
-use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+use crate::validate::Diagnostic;
 
 /// All possible error returned from this SDK defined as variants of this enum.
-/// 
-/// This also derives the failure::Fail trait, so it should be easier to handle and extend
-/// in clients that also support this failure crate.
-#[derive(Debug, Fail, Deserialize)]
+///
+/// Failures reported by the api carry the HTTP status code and the raw response body,
+/// so callers can branch on the kind of failure instead of matching on messages.
+/// The enum implements `std::error::Error`.
+#[derive(Debug)]
 pub enum ApiError {
-  
-  #[fail(display = "Invalid api_key")]
+
+  /// The api key is empty
   InvalidApiKey(),
- 
+
   /// A generic error with message on a possible failure while interacting with the api
-  #[fail(display = "Error: {}", _0)]
   GenericError(String),
 
-  
+  /// The api rejected the credentials (401 or 403)
+  Unauthorized { status: u16, message: String, body: String },
+
+  /// The requested bot or resource does not exist (404)
+  NotFound { status: u16, message: String, body: String },
+
+  /// Too many requests (429), `retry_after` holds the delay announced by the server if any
+  RateLimited { status: u16, retry_after: Option<Duration>, message: String, body: String },
+
+  /// The request payload was rejected (400 or 422), `details` lists the offending fields
+  Validation { status: u16, message: String, details: Vec<FieldError>, body: String },
+
+  /// The api failed to handle the request (5xx)
+  Server { status: u16, message: String, body: String },
+
+  /// Any other non-success status
+  Http { status: u16, message: String, body: String },
+
+  /// The request never got a response: connection, timeout or TLS failure
+  Transport(String),
+
+  /// The response could not be decoded into the expected type
+  Decode { message: String, body: String },
+
+  /// The bot changed since the `updated_at` a patch expected
+  Conflict { expected: String, actual: String },
+
+  /// The bot definition failed local validation and was not sent
+  InvalidSpec(Vec<Diagnostic>),
 }
 
+/// A single field level problem reported by a validation failure
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+  /// Path to the offending field, e.g. `["body", "name"]`
+  pub location: Vec<String>,
+  pub message: String,
+  /// Machine readable kind of the failure when the api provides one
+  pub kind: Option<String>,
+}
+
+impl ApiError {
+  /// Builds the error matching an unsuccessful status code
+  pub(crate) fn from_status(status: u16, message: String, details: Vec<FieldError>, retry_after: Option<Duration>, body: String) -> ApiError {
+    match status {
+      401 | 403 => ApiError::Unauthorized { status, message, body },
+      404 => ApiError::NotFound { status, message, body },
+      429 => ApiError::RateLimited { status, retry_after, message, body },
+      400 | 422 => ApiError::Validation { status, message, details, body },
+      500..=599 => ApiError::Server { status, message, body },
+      _ => ApiError::Http { status, message, body },
+    }
+  }
+
+  /// HTTP status code of the failed response, `None` if no response was received
+  pub fn status(&self) -> Option<u16> {
+    match self {
+      ApiError::Unauthorized { status, .. }
+      | ApiError::NotFound { status, .. }
+      | ApiError::RateLimited { status, .. }
+      | ApiError::Validation { status, .. }
+      | ApiError::Server { status, .. }
+      | ApiError::Http { status, .. } => Some(*status),
+      _ => None,
+    }
+  }
+
+  /// Raw body of the failed response
+  pub fn body(&self) -> Option<&str> {
+    match self {
+      ApiError::Unauthorized { body, .. }
+      | ApiError::NotFound { body, .. }
+      | ApiError::RateLimited { body, .. }
+      | ApiError::Validation { body, .. }
+      | ApiError::Server { body, .. }
+      | ApiError::Http { body, .. }
+      | ApiError::Decode { body, .. } => Some(body),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for ApiError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ApiError::InvalidApiKey() => write!(f, "Invalid api_key"),
+      ApiError::GenericError(message) => write!(f, "Error: {}", message),
+      ApiError::Unauthorized { status, message, .. } => write!(f, "Unauthorized ({}): {}", status, message),
+      ApiError::NotFound { status, message, .. } => write!(f, "Not found ({}): {}", status, message),
+      ApiError::RateLimited { status, retry_after: Some(delay), message, .. } => {
+        write!(f, "Rate limited ({}), retry after {}s: {}", status, delay.as_secs(), message)
+      }
+      ApiError::RateLimited { status, message, .. } => write!(f, "Rate limited ({}): {}", status, message),
+      ApiError::Validation { status, message, details, .. } => {
+        write!(f, "Validation failed ({}): {}", status, message)?;
+        for detail in details {
+          write!(f, "; {}: {}", detail.location.join("."), detail.message)?;
+        }
+        Ok(())
+      }
+      ApiError::Server { status, message, .. } => write!(f, "Server error ({}): {}", status, message),
+      ApiError::Http { status, message, .. } => write!(f, "HTTP error ({}): {}", status, message),
+      ApiError::Transport(message) => write!(f, "Transport error: {}", message),
+      ApiError::Decode { message, .. } => write!(f, "Failed to decode response: {}", message),
+      ApiError::Conflict { expected, actual } => {
+        write!(f, "Bot was modified at {} since the expected version {}", actual, expected)
+      }
+      ApiError::InvalidSpec(diagnostics) => {
+        write!(f, "Invalid bot definition")?;
+        for diagnostic in diagnostics {
+          write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl std::error::Error for ApiError {}
+
 impl From<reqwest::Error> for ApiError {
   fn from(req_err: reqwest::Error) -> ApiError {
-    ApiError::GenericError(format!("{}", req_err))
+    if req_err.is_decode() {
+      ApiError::Decode { message: format!("{}", req_err), body: String::new() }
+    } else {
+      ApiError::Transport(format!("{}", req_err))
+    }
   }
 }
 
@@ -31,6 +153,7 @@ impl From<std::io::Error> for ApiError {
   }
 }
 
+#[cfg(feature = "fs-import")]
 impl From<walkdir::Error> for ApiError {
   fn from(io_err: walkdir::Error) -> ApiError {
     ApiError::GenericError(format!("{}", io_err))
@@ -39,6 +162,6 @@ impl From<walkdir::Error> for ApiError {
 
 impl From<serde_json::Error> for ApiError {
   fn from(json_err: serde_json::Error) -> ApiError {
-    ApiError::GenericError(format!("{}", json_err))
+    ApiError::Decode { message: format!("{}", json_err), body: String::new() }
   }
 }
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+/// Name of the terminal state of every flow
+pub const END_STATE: &str = "end";
+
+/// Conversation flow of a bot: flow states keyed by their name.
+///
+/// Intents enter the flow at the state carrying their name, each state then
+/// moves on to its `next_state` or, for choice states, to the state mapped to
+/// the option the user picked.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Flow(HashMap<String, FlowState>);
+
+/// A single state of a [`Flow`].
+///
+/// ```json
+/// "choose_size": {
+///     "message": ["Which size?", "1. Small", "2. Large"],
+///     "1": "small_pizza",
+///     "2": "large_pizza",
+///     "fallback_message": ["Please pick 1 or 2"]
+/// }
+/// ```
+///
+/// Numbered keys holding a string are choices mapping the user's answer to the next state,
+/// any other field is kept in `extra` so a state survives a round trip unchanged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowState {
+    pub message: Vec<MessageBlock>,
+    pub next_state: Option<String>,
+    /// Options offered to the user mapped to the state they lead to
+    pub choices: HashMap<String, String>,
+    /// Sent when the answer matches none of the `choices`
+    pub fallback_message: Vec<MessageBlock>,
+    pub extra: Map<String, Value>,
+}
+
+/// One entry of a `message` list.
+///
+/// Flows written for the dashboard use plain strings, exported bots often wrap
+/// them in nested lists, and rich content such as `{"send_images": [...]}` is kept as is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageBlock {
+    Text(String),
+    Group(Vec<MessageBlock>),
+    Structured(Value),
+}
+
+impl Flow {
+    pub fn new() -> Flow {
+        Flow::default()
+    }
+
+    /// Adds or replaces a state, returns the replaced one
+    pub fn insert_state<S: Into<String>>(&mut self, name: S, state: FlowState) -> Option<FlowState> {
+        self.0.insert(name.into(), state)
+    }
+
+    /// Builder style variant of [`Flow::insert_state`]
+    pub fn with_state<S: Into<String>>(mut self, name: S, state: FlowState) -> Flow {
+        self.insert_state(name, state);
+        self
+    }
+
+    /// State names sorted alphabetically, handy for stable output
+    pub fn state_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn into_inner(self) -> HashMap<String, FlowState> {
+        self.0
+    }
+}
+
+impl Deref for Flow {
+    type Target = HashMap<String, FlowState>;
+
+    fn deref(&self) -> &HashMap<String, FlowState> {
+        &self.0
+    }
+}
+
+impl DerefMut for Flow {
+    fn deref_mut(&mut self) -> &mut HashMap<String, FlowState> {
+        &mut self.0
+    }
+}
+
+impl From<HashMap<String, FlowState>> for Flow {
+    fn from(states: HashMap<String, FlowState>) -> Flow {
+        Flow(states)
+    }
+}
+
+impl FromIterator<(String, FlowState)> for Flow {
+    fn from_iter<I: IntoIterator<Item = (String, FlowState)>>(iter: I) -> Flow {
+        Flow(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Flow {
+    type Item = (String, FlowState);
+    type IntoIter = std::collections::hash_map::IntoIter<String, FlowState>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl FlowState {
+    /// A state sending the given texts
+    pub fn new<I, S>(messages: I) -> FlowState
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        FlowState {
+            message: messages.into_iter().map(|m| MessageBlock::Text(m.into())).collect(),
+            ..FlowState::default()
+        }
+    }
+
+    /// Sets the state to move to after this one
+    pub fn next<S: Into<String>>(mut self, next_state: S) -> FlowState {
+        self.next_state = Some(next_state.into());
+        self
+    }
+
+    /// Adds an option leading to `next_state`, options are menu numbers such as `"1"`
+    pub fn choice<K: Into<String>, S: Into<String>>(mut self, option: K, next_state: S) -> FlowState {
+        self.choices.insert(option.into(), next_state.into());
+        self
+    }
+
+    /// Sets the message sent when the answer matches none of the choices
+    pub fn fallback<I, S>(mut self, messages: I) -> FlowState
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fallback_message = messages.into_iter().map(|m| MessageBlock::Text(m.into())).collect();
+        self
+    }
+
+    /// Whether the user has to pick one of the `choices` in this state
+    pub fn is_choice(&self) -> bool {
+        !self.choices.is_empty()
+    }
+
+    /// Every state this one can lead to, `next_state` first then the choices sorted by option
+    pub fn transitions(&self) -> Vec<&str> {
+        let mut options: Vec<(&String, &String)> = self.choices.iter().collect();
+        options.sort();
+
+        self.next_state
+            .iter()
+            .map(String::as_str)
+            .chain(options.into_iter().map(|(_, state)| state.as_str()))
+            .collect()
+    }
+
+    /// All texts of `message` in order, nested groups flattened
+    pub fn texts(&self) -> Vec<&str> {
+        MessageBlock::texts_of(&self.message)
+    }
+}
+
+impl MessageBlock {
+    /// Texts of a list of blocks in order, nested groups flattened
+    pub fn texts_of(blocks: &[MessageBlock]) -> Vec<&str> {
+        let mut texts = Vec::new();
+        for block in blocks {
+            block.collect_texts(&mut texts);
+        }
+        texts
+    }
+
+    fn collect_texts<'a>(&'a self, texts: &mut Vec<&'a str>) {
+        match self {
+            MessageBlock::Text(text) => texts.push(text),
+            MessageBlock::Group(blocks) => blocks.iter().for_each(|b| b.collect_texts(texts)),
+            MessageBlock::Structured(_) => {}
+        }
+    }
+}
+
+impl From<&str> for MessageBlock {
+    fn from(text: &str) -> MessageBlock {
+        MessageBlock::Text(text.to_owned())
+    }
+}
+
+impl Serialize for FlowState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut object = self.extra.clone();
+
+        for (option, state) in &self.choices {
+            object.insert(option.clone(), Value::String(state.clone()));
+        }
+
+        if !self.message.is_empty() {
+            object.insert("message".to_owned(), serde_json::to_value(&self.message).map_err(serde::ser::Error::custom)?);
+        }
+
+        if let Some(next_state) = &self.next_state {
+            object.insert("next_state".to_owned(), Value::String(next_state.clone()));
+        }
+
+        if !self.fallback_message.is_empty() {
+            object.insert(
+                "fallback_message".to_owned(),
+                serde_json::to_value(&self.fallback_message).map_err(serde::ser::Error::custom)?,
+            );
+        }
+
+        object.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FlowState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FlowState, D::Error> {
+        let object = Map::<String, Value>::deserialize(deserializer)?;
+        let mut state = FlowState::default();
+
+        for (key, value) in object {
+            match key.as_str() {
+                "message" => state.message = blocks(value).map_err(D::Error::custom)?,
+                "fallback_message" => state.fallback_message = blocks(value).map_err(D::Error::custom)?,
+                "next_state" => match value {
+                    Value::String(next_state) => state.next_state = Some(next_state),
+                    Value::Null => {}
+                    other => return Err(D::Error::custom(format!("next_state must be a string, got {}", other))),
+                },
+                _ => match value {
+                    Value::String(next_state) if is_option(&key) => {
+                        state.choices.insert(key, next_state);
+                    }
+                    other => {
+                        state.extra.insert(key, other);
+                    }
+                },
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+/// Choice keys are the numbers of a menu, `"1"`, `"2"`, ...
+fn is_option(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// A `message` is usually a list but a single string is accepted too
+fn blocks(value: Value) -> Result<Vec<MessageBlock>, serde_json::Error> {
+    match value {
+        Value::Array(_) => serde_json::from_value(value),
+        Value::Null => Ok(Vec::new()),
+        single => Ok(vec![serde_json::from_value(single)?]),
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use crate::bot::Bot;
+use crate::flow::{Flow, END_STATE};
+use crate::spec::BotSpec;
+
+/// The flow of a bot as a directed graph: intents lead to the state named after
+/// them, states lead to their `next_state` and to the targets of their choices,
+/// and every path ends in the terminal `end` node.
+///
+/// Targets that are not states of the flow become [`NodeKind::Missing`] nodes, so
+/// broken transitions stand out in the rendered graph.
+///
+/// ```
+/// use sarufi::{BotSpec, FlowGraph, FlowState};
+///
+/// let spec = BotSpec::new("Pizza bot")
+///     .intent("order_pizza", ["I want a pizza"])
+///     .state("order_pizza", FlowState::new(["Which size?"]).choice("1", "small").choice("2", "large"))
+///     .state("small", FlowState::new(["Small it is"]).next("end"))
+///     .state("large", FlowState::new(["Large it is"]).next("end"));
+///
+/// let graph = FlowGraph::from_spec(&spec);
+/// println!("{}", graph.to_dot());
+/// println!("{}", graph.to_mermaid());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowGraph {
+    pub name: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    /// Unique within the graph, `intent:<name>`, `state:<name>` or `end`
+    pub id: String,
+    pub label: String,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Intent,
+    State,
+    /// The terminal `end` state
+    End,
+    /// A transition target the flow does not define
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+    /// The user answer selecting a choice
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// An intent starting the state named after it
+    Trigger,
+    /// A `next_state` transition
+    Next,
+    /// A choice transition
+    Choice,
+}
+
+impl FlowGraph {
+    pub fn from_bot(bot: &Bot) -> FlowGraph {
+        FlowGraph::new(&bot.name, &bot.intents, &bot.flows)
+    }
+
+    pub fn from_spec(spec: &BotSpec) -> FlowGraph {
+        FlowGraph::new(&spec.name, spec.intents.as_ref().unwrap_or(&HashMap::new()), spec.flow.as_ref().unwrap_or(&Flow::default()))
+    }
+
+    /// Builds the graph of a flow, nodes and edges come in name order
+    pub fn new(name: &str, intents: &HashMap<String, Vec<String>>, flow: &Flow) -> FlowGraph {
+        let mut nodes = BTreeMap::new();
+        let mut edges = Vec::new();
+        let mut targets = BTreeSet::new();
+
+        let intent_names: BTreeSet<&String> = intents.keys().collect();
+        for intent in intent_names {
+            let id = format!("intent:{}", intent);
+            nodes.insert(id.clone(), GraphNode { id: id.clone(), label: intent.clone(), kind: NodeKind::Intent });
+            if flow.contains_key(intent) {
+                edges.push(GraphEdge { from: id, to: state_id(intent), kind: EdgeKind::Trigger, label: None });
+            }
+        }
+
+        for name in flow.state_names() {
+            let state = &flow[name];
+            let id = state_id(name);
+            nodes.insert(id.clone(), GraphNode { id: id.clone(), label: name.to_owned(), kind: NodeKind::State });
+
+            let choices: BTreeMap<&String, &String> = state.choices.iter().collect();
+            for (answer, target) in choices {
+                edges.push(GraphEdge { from: id.clone(), to: state_id(target), kind: EdgeKind::Choice, label: Some(answer.clone()) });
+                targets.insert(target.as_str());
+            }
+            if let Some(next_state) = &state.next_state {
+                edges.push(GraphEdge { from: id.clone(), to: state_id(next_state), kind: EdgeKind::Next, label: None });
+                targets.insert(next_state.as_str());
+            }
+        }
+
+        for target in targets {
+            let id = state_id(target);
+            if target == END_STATE {
+                nodes.insert(id.clone(), GraphNode { id, label: END_STATE.to_owned(), kind: NodeKind::End });
+            } else if !flow.contains_key(target) {
+                nodes.insert(id.clone(), GraphNode { id, label: target.to_owned(), kind: NodeKind::Missing });
+            }
+        }
+
+        // intents first, then states, `end` last
+        let mut nodes: Vec<GraphNode> = nodes.into_values().collect();
+        nodes.sort_by_key(|node| match node.kind {
+            NodeKind::Intent => 0,
+            NodeKind::State | NodeKind::Missing => 1,
+            NodeKind::End => 2,
+        });
+
+        FlowGraph { name: name.to_owned(), nodes, edges }
+    }
+
+    /// Renders the graph in the Graphviz DOT language
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph {} {{", dot_quote(&self.name)).unwrap();
+        writeln!(out, "    rankdir=LR;").unwrap();
+        writeln!(out, "    node [shape=box, style=rounded];").unwrap();
+
+        for node in &self.nodes {
+            let attributes = match node.kind {
+                NodeKind::Intent => ", shape=ellipse, style=filled, fillcolor=lightblue",
+                NodeKind::State => "",
+                NodeKind::End => ", shape=doublecircle",
+                NodeKind::Missing => ", color=red, fontcolor=red, style=dashed",
+            };
+            writeln!(out, "    {} [label={}{}];", dot_quote(&node.id), dot_quote(&node.label), attributes).unwrap();
+        }
+
+        for edge in &self.edges {
+            let attributes = match (edge.kind, &edge.label) {
+                (EdgeKind::Trigger, _) => " [style=dashed]".to_owned(),
+                (_, Some(label)) => format!(" [label={}]", dot_quote(label)),
+                (_, None) => String::new(),
+            };
+            writeln!(out, "    {} -> {}{};", dot_quote(&edge.from), dot_quote(&edge.to), attributes).unwrap();
+        }
+
+        out.push('}');
+        out
+    }
+
+    /// Renders the graph as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        // Mermaid ids must be plain words, nodes are numbered in order
+        let ids: HashMap<&str, String> = self.nodes.iter().enumerate().map(|(i, node)| (node.id.as_str(), format!("n{}", i))).collect();
+
+        let mut out = String::from("flowchart LR\n");
+        for node in &self.nodes {
+            let label = mermaid_quote(&node.label);
+            let shape = match node.kind {
+                NodeKind::Intent => format!("([{}])", label),
+                NodeKind::State => format!("[{}]", label),
+                NodeKind::End => format!("((({})))", label),
+                NodeKind::Missing => format!("[{}]:::missing", mermaid_quote(&format!("{} (missing)", node.label))),
+            };
+            writeln!(out, "    {}{}", ids[node.id.as_str()], shape).unwrap();
+        }
+
+        for edge in &self.edges {
+            let arrow = match (edge.kind, &edge.label) {
+                (EdgeKind::Trigger, _) => "-.->".to_owned(),
+                (_, Some(label)) => format!("-->|{}|", mermaid_quote(label)),
+                (_, None) => "-->".to_owned(),
+            };
+            writeln!(out, "    {} {} {}", ids[edge.from.as_str()], arrow, ids[edge.to.as_str()]).unwrap();
+        }
+
+        if self.nodes.iter().any(|node| node.kind == NodeKind::Missing) {
+            writeln!(out, "    classDef missing stroke:#d00,stroke-dasharray:4").unwrap();
+        }
+        out.trim_end().to_owned()
+    }
+}
+
+fn state_id(name: &str) -> String {
+    if name == END_STATE {
+        END_STATE.to_owned()
+    } else {
+        format!("state:{}", name)
+    }
+}
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn mermaid_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
@@ -1,361 +1,380 @@
 
-use reqwest::{Client, ClientBuilder, header::HeaderMap};
+use reqwest::{Method, header::HeaderMap};
+use std::sync::Arc;
 
-pub use errors::ApiError;
+pub use errors::{ApiError, FieldError};
 pub use bot::{Bot};
+pub use builder::SarufiBuilder;
+pub use flow::{Flow, FlowState, MessageBlock, END_STATE};
+pub use conversation::{Channel, ConversationResponse, Media, MediaKind, Message};
+pub use retry::RetryPolicy;
+pub use spec::BotSpec;
+pub use patch::BotPatch;
+pub use reconcile::{Plan, ReconcileOptions, Step};
+pub use diff::{diff, diff_spec, diff_specs, BotDiff, Change, ChangeKind, Section};
+pub use engine::FlowEngine;
+pub use graph::{EdgeKind, FlowGraph, GraphEdge, GraphNode, NodeKind};
+pub use validate::{has_errors, validate, Diagnostic, Severity};
+#[cfg(feature = "fs-import")]
+pub use validate::validate_file;
+#[cfg(feature = "fs-import")]
+pub use project::BotProject;
+#[cfg(feature = "fs-import")]
+pub use snapshot::{Snapshot, SnapshotStore};
+pub use session::{Conversation, Turn};
+pub use cassette::{Cassette, Interaction, RecordedRequest, RecordedResponse};
+pub use async_trait::async_trait;
+pub use transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport, TransportError, TransportErrorKind};
 use serde_json::{ Value};
 use std::{collections::HashMap};
-use std::fs::File;
-use std::io::BufReader;
 
 mod errors;
 mod utils;
 mod api;
 mod bot;
+mod builder;
+mod cassette;
+mod conversation;
+mod flow;
+pub mod whatsapp;
+mod retry;
+mod session;
+mod spec;
+mod transport;
+mod patch;
+mod reconcile;
+mod diff;
+mod engine;
+mod graph;
+#[cfg(feature = "fs-import")]
+mod project;
+#[cfg(feature = "fs-import")]
+mod snapshot;
+mod validate;
+pub mod repl;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+#[cfg(test)]
 mod test;
 
-use crate::api::SarufiApiError;
-
 
 
 /// API struct. Exposes function to interact with the Sarufi API 🥷
-/// #[derive(Debug, Serialize, Deserialize)]
+///
+/// Cloning is cheap, clones share the same transport and connection pool.
+#[derive(Clone)]
 pub struct Sarufi {
-    client: Client,
+    transport: Arc<dyn Transport>,
+    base_url: String,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+    validate_bots: bool,
+    cassette: Option<Arc<Cassette>>,
 }
 
 
 impl Sarufi {
     /// Creates a new instance of Sarufi using the provided api key
-    /// this function panics if the api_key is empty 🤒
+    /// this function fails if the api_key is empty 🤒
     pub fn new<S: Into<String>>(api_key: S) -> Result<Sarufi, ApiError> {
-        let owned_key = api_key.into();
-
-        utils::validate_keys(&owned_key)?;
+        SarufiBuilder::new(api_key).build()
+    }
 
-        let mut default_headers = HeaderMap::new();
-        default_headers.insert("Authorization", format!("Bearer {}", owned_key).parse().unwrap());
-        default_headers.insert("Content-Type", "application/json".parse().unwrap());
+    /// Creates a client from the `SARUFI_API_KEY` and `SARUFI_BASE_URL` environment variables
+    /// or a `.env` file, see [`SarufiBuilder::from_env`]
+    #[cfg(feature = "env-config")]
+    pub fn from_env() -> Result<Sarufi, ApiError> {
+        SarufiBuilder::from_env()?.build()
+    }
 
+    /// Starts a [`SarufiBuilder`] to configure base url, timeouts, proxy, headers, retries or a custom client
+    pub fn builder<S: Into<String>>(api_key: S) -> SarufiBuilder {
+        SarufiBuilder::new(api_key)
+    }
 
-        let client = ClientBuilder::new().default_headers(default_headers).build()?;
+    /// The url every endpoint path is resolved against
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
 
-        Ok(Sarufi { client })
+    /// The cassette the traffic is recorded to or replayed from
+    pub fn cassette(&self) -> Option<&Cassette> {
+        self.cassette.as_deref()
     }
 
-    
-    pub async fn get_bot(&self, id: usize) -> Result<Bot, ApiError> {
-            let url = utils::api_url(&format!("/chatbot/{}", id));
-            
-            let response = self.client.get(&url).send().await?;
-
-            if response.status().is_success() {
-                let  result = response.json::<Bot>().await?;
-                Ok(result)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
+    /// Rejects invalid definitions when validation is enabled on the builder
+    fn check_spec(&self, spec: &BotSpec) -> Result<(), ApiError> {
+        if self.validate_bots {
+            let diagnostics = spec.validate();
+            if validate::has_errors(&diagnostics) {
+                return Err(ApiError::InvalidSpec(diagnostics));
             }
-  
         }
+        Ok(())
+    }
 
-    pub async fn get_all_bots(&self) -> Result<Vec<Bot>, ApiError> {
-            let url = utils::api_url("/chatbots");
-            let response = self.client.get(&url).send().await?;
-
-            if response.status().is_success() {
-                let result = response.json::<Vec<Bot>>().await?;
-                Ok(result)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
-            }
-  
-        }
-     
-        pub async fn _fetch_response(&self, bot_id: usize, chat_id: &str, message: &str, message_type: &str, channel: &str) -> Result<String, ApiError> {
-            let _url = utils::api_url("/conversation");
-        
-            if  channel == "whatsapp" {
-                let _url = utils::api_url("/conversation/whatsapp");  
-            }
+    /// Sends a request through the transport and the retry policy, any non-success status ends up as an [`ApiError`]
+    async fn send(&self, method: Method, path: &str, body: Option<&Value>) -> Result<HttpResponse, ApiError> {
+        let idempotent = self.retry.is_idempotent(&method);
+        let request = HttpRequest {
+            method: method.clone(),
+            url: utils::api_url(&self.base_url, path),
+            path: path.to_owned(),
+            headers: self.headers.clone(),
+            body: body.map(serde_json::to_vec).transpose()?,
+        };
+        let mut attempt = 1;
+
+        loop {
+            let delay = match self.transport.send(request.clone()).await {
+                Ok(response) if response.is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status;
+                    let retryable = self.retry.retry_statuses.contains(&status) && (idempotent || status == 429);
+
+                    if !retryable || attempt >= self.retry.max_attempts {
+                        return Err(api::error_from_response(response));
+                    }
+
+                    match self.retry.delay(attempt, api::retry_after(&response)) {
+                        Some(delay) => delay,
+                        None => return Err(api::error_from_response(response)),
+                    }
+                }
+                Err(e) => {
+                    let retryable = e.kind == TransportErrorKind::Connect
+                        || (idempotent && matches!(e.kind, TransportErrorKind::Timeout | TransportErrorKind::Request));
+
+                    if !retryable || attempt >= self.retry.max_attempts {
+                        return Err(e.into());
+                    }
 
-            let mut data = HashMap::new();
-            data.insert("bot_id".to_owned(), Value::Number(serde_json::Number::from(bot_id)));
-            data.insert("chat_id".to_owned(), Value::String(chat_id.to_owned()));
-            data.insert("message".to_owned(), Value::String(message.to_owned()));
-            data.insert("message_type".to_owned(), Value::String(message_type.to_owned()));
-            data.insert("channel".to_owned(), Value::String(channel.to_owned()));
-        
-            let response = self.client.post(&_url).json(&Value::Object(data.into_iter().collect())).send().await?;
-        
-            if response.status().is_success() {
-               
-                let json_string = response.text().await.unwrap();
-                let json_value: Value = serde_json::from_str(&json_string).unwrap();
-                let result = & json_value["message"][0];
-               
-                Ok(result.to_string())
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
+                    self.retry.delay(attempt, None).unwrap_or_default()
+                }
+            };
+
+            log::debug!("retrying {} {} in {:?} (attempt {})", method, path, delay, attempt + 1);
+            // `Option::is_none_or` needs Rust 1.82
+            #[allow(clippy::unnecessary_map_or)]
+            let live = self.cassette.as_deref().map_or(true, Cassette::is_recording);
+            if live {
+                tokio::time::sleep(delay).await;
             }
+            attempt += 1;
         }
-        
-        pub async fn chat(&self, bot_id: usize) -> Result<String, ApiError> {
-            let chat_id = utils::generate_uuid().to_string();
-            println!("Chat ID: {:?}", chat_id);
-            let message = "Hello";
-            let message_type = "text";
-            let channel = "general";
+    }
 
-            let response = self._fetch_response(bot_id, &chat_id, message, message_type, channel).await.unwrap();
+    pub async fn get_bot(&self, id: usize) -> Result<Bot, ApiError> {
+        let response = self.send(Method::GET, &format!("/chatbot/{}", id), None).await?;
+        api::decode::<Bot>(response)
+    }
 
-            Ok(response)
-        }
+    pub async fn get_all_bots(&self) -> Result<Vec<Bot>, ApiError> {
+        let response = self.send(Method::GET, "/chatbots", None).await?;
+        api::decode::<Vec<Bot>>(response)
+    }
 
-        pub async fn chat_status(&self, bot_id: usize, chat_id: &str) -> Result<String, ApiError> {
-            let url = utils::api_url("/allchannels/status");
-        
-            let mut data = HashMap::new();
-            data.insert("bot_id".to_owned(), Value::Number(serde_json::Number::from(bot_id)));
-            data.insert("chat_id".to_owned(), Value::String(chat_id.to_owned()));
-        
-            let response = self.client.post(&url).json(&Value::Object(data.into_iter().collect())).send().await?;
-        
-            if response.status().is_success() {
-                let json_string = response.text().await.unwrap();
-                Ok(json_string)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
-            }
-        }
+    /// Sends a message to a bot and returns its full reply
+    ///
+    /// The channel selects the endpoint, WhatsApp traffic goes to `/conversation/whatsapp`
+    pub async fn respond<C: Into<Channel>>(&self, bot_id: usize, chat_id: &str, message: &str, message_type: &str, channel: C) -> Result<ConversationResponse, ApiError> {
+        let channel = channel.into();
 
-        pub async fn update_conversation_state(&self, bot_id: usize, chat_id: &str, next_state: &str) -> Result<String, ApiError> {
-
-            let url = utils::api_url("/conversation-state");
-        
-            let mut data = HashMap::new();
-            data.insert("bot_id".to_owned(), Value::Number(serde_json::Number::from(bot_id)));
-            data.insert("chat_id".to_owned(), Value::String(chat_id.to_owned()));
-            data.insert("next_state".to_owned(), Value::String(next_state.to_owned()));
-
-            let response = self.client.post(&url).json(&Value::Object(data.into_iter().collect())).send().await?;
-
-            if response.status().is_success() {
-                let json_string = response.text().await.unwrap();
-                // let json_value: Value = serde_json::from_str(&json_string).unwrap();
-                // let result = & json_value["message"][0];
-               
-                Ok(json_string)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
-            }
-            
-        
-        }
-        pub async fn delete_bot(&self, id: usize) -> Result<(), ApiError> {
-            let url = utils::api_url(&format!("/chatbot/{}", id));
-            let response = self.client.delete(&url).send().await?;
-
-            if response.status().is_success() {
-     
-                Ok(())
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
-            }
-  
-        }
+        let mut data = HashMap::new();
+        data.insert("bot_id".to_owned(), Value::Number(serde_json::Number::from(bot_id)));
+        data.insert("chat_id".to_owned(), Value::String(chat_id.to_owned()));
+        data.insert("message".to_owned(), Value::String(message.to_owned()));
+        data.insert("message_type".to_owned(), Value::String(message_type.to_owned()));
+        data.insert("channel".to_owned(), Value::String(channel.as_str().to_owned()));
 
-        /// Creates a new bot
-        pub async fn create_bot(&self, 
-            name: &str,
-            description: Option<&str>,
-            industry: Option<&str>,
-            flow: Option<HashMap<String, Value>>,
-            intents: Option<HashMap<String, Vec<String>>>,
-            webhook_url: Option<&str>,
-            webhook_trigger_intents: Option<Vec<String>>,
-            visible_on_community: Option<bool>) -> Result<Bot, ApiError> {
-
-            let url = utils::api_url("/chatbot");
-            let mut data = HashMap::new();
-
-            data.insert("name".to_owned(), Value::String(name.to_owned()));
-    
-            if let Some(description) = description {
-                data.insert("description".to_owned(), Value::String(description.to_owned()));
-            }
-        
-            if let Some(industry) = industry {
-                data.insert("industry".to_owned(), Value::String(industry.to_owned()));
-            }
-        
-            if let Some(flow) = flow {
-                data.insert("flow".to_owned(), Value::Object(flow.into_iter().collect()));
-            }
-        
-            if let Some(intents) = intents {
-                data.insert(
-                    "intents".to_owned(),
-                    Value::Object(
-                        intents
-                            .into_iter()
-                            .map(|(k, v)| (k, Value::Array(v.into_iter().map(Value::String).collect())))
-                            .collect(),
-                    ),
-                );
-            }
-        
-            if let Some(webhook_url) = webhook_url {
-                data.insert("webhook_url".to_owned(), Value::String(webhook_url.to_owned()));
-            }
-        
-            if let Some(webhook_trigger_intents) = webhook_trigger_intents {
-                data.insert(
-                    "webhook_trigger_intents".to_owned(),
-                    Value::Array(webhook_trigger_intents.into_iter().map(Value::String).collect()),
-                );
-            }
+        let response = self.send(Method::POST, channel.endpoint(), Some(&Value::Object(data.into_iter().collect()))).await?;
 
-            if let Some(visible_on_community) = visible_on_community {
-                data.insert("visible_on_community".to_owned(), Value::Bool(visible_on_community));
-            }
-        
-            let response = self.client.post(&url).json(&Value::Object(data.into_iter().collect())).send().await?;
-            
-         
-            if response.status().is_success() {
-                
-                let mut result = response.json::<Bot>().await?;
-
-                // do you really need to check this?, seems to be working fine without it
-                if let Some(e_metrics) = result.evaluation_metrics {
-                    
-                    result.evaluation_metrics = Some(e_metrics);
-                }
+        let json_value = api::decode::<Value>(response)?;
+        Ok(ConversationResponse::from_value(json_value))
+    }
 
-                if let Some(confidence_threshold) = result.confidence_threshold {
-                    
-                    result.confidence_threshold = Some(confidence_threshold);
-                }
-                // let json_string = response.text().await?;
-                // let json_value: Value = serde_json::from_str(&json_string).unwrap();
-                // let pretty_json = serde_json::to_string_pretty(&json_value).unwrap();
-                // println!("{}", pretty_json);
-               
-                Ok(result)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
-            }
-            
+    /// Sends a text message on the general channel
+    pub async fn send_message(&self, bot_id: usize, chat_id: &str, message: &str) -> Result<ConversationResponse, ApiError> {
+        self.respond(bot_id, chat_id, message, "text", Channel::General).await
+    }
 
-        }
+    /// Returns only the first message of the reply as a JSON string, prefer [`Sarufi::respond`]
+    pub async fn _fetch_response(&self, bot_id: usize, chat_id: &str, message: &str, message_type: &str, channel: &str) -> Result<String, ApiError> {
+        let response = self.respond(bot_id, chat_id, message, message_type, channel).await?;
+        let result = & response.raw["message"][0];
 
+        Ok(result.to_string())
+    }
 
-        pub async fn create_bot_from_file(
-            &self,
-            file_path: &str,
-        ) -> Result<Bot, ApiError> {
-            let file = File::open(file_path)?;
-            let reader = BufReader::new(file);
-            let data: Value = serde_json::from_reader(reader)?;
-            let data = data.as_object().ok_or_else(|| ApiError::GenericError("Invalid JSON".to_owned()))?;
-            print!("{:?}", data);
-        
-            let url = utils::api_url("/chatbot");
-            let response = self.client.post(&url).json(&data).send().await?;
-        
-            if response.status().is_success() {
-                let mut result = response.json::<Bot>().await?;
-        
-                if let Some(e_metrics) = result.evaluation_metrics {
-                    result.evaluation_metrics = Some(e_metrics);
-                }
-        
-                if let Some(confidence_threshold) = result.confidence_threshold {
-                    result.confidence_threshold = Some(confidence_threshold);
-                }
-        
-                Ok(result)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
+    /// Starts a conversation with a bot, see [`Conversation`]
+    pub fn conversation(&self, bot_id: usize) -> Conversation<'_> {
+        Conversation::new(self, bot_id)
+    }
+
+    /// Says "Hello" to a bot on a fresh chat and returns the first message of its reply,
+    /// use [`Sarufi::conversation`] to hold a multi-turn chat
+    pub async fn chat(&self, bot_id: usize) -> Result<String, ApiError> {
+        let mut conversation = self.conversation(bot_id);
+        log::debug!("Chat ID: {:?}", conversation.chat_id());
+
+        let response = conversation.send("Hello").await?;
+
+        Ok(response.raw["message"][0].to_string())
+    }
+
+    pub async fn chat_status(&self, bot_id: usize, chat_id: &str) -> Result<String, ApiError> {
+        let mut data = HashMap::new();
+        data.insert("bot_id".to_owned(), Value::Number(serde_json::Number::from(bot_id)));
+        data.insert("chat_id".to_owned(), Value::String(chat_id.to_owned()));
+
+        let response = self.send(Method::POST, "/allchannels/status", Some(&Value::Object(data.into_iter().collect()))).await?;
+
+        Ok(response.text())
+    }
+
+    pub async fn update_conversation_state(&self, bot_id: usize, chat_id: &str, next_state: &str) -> Result<String, ApiError> {
+
+        let mut data = HashMap::new();
+        data.insert("bot_id".to_owned(), Value::Number(serde_json::Number::from(bot_id)));
+        data.insert("chat_id".to_owned(), Value::String(chat_id.to_owned()));
+        data.insert("next_state".to_owned(), Value::String(next_state.to_owned()));
+
+        let response = self.send(Method::POST, "/conversation-state", Some(&Value::Object(data.into_iter().collect()))).await?;
+
+        Ok(response.text())
+    }
+
+    pub async fn delete_bot(&self, id: usize) -> Result<(), ApiError> {
+        self.send(Method::DELETE, &format!("/chatbot/{}", id), None).await?;
+        Ok(())
+    }
+
+    /// Creates a new bot from its definition
+    pub async fn create_bot(&self, spec: &BotSpec) -> Result<Bot, ApiError> {
+        self.check_spec(spec)?;
+        let data = serde_json::to_value(spec)?;
+        let response = self.send(Method::POST, "/chatbot", Some(&data)).await?;
+
+        api::decode::<Bot>(response)
+    }
+
+    /// Creates a new bot from a JSON definition file
+    #[cfg(feature = "fs-import")]
+    pub async fn create_bot_from_file(
+        &self,
+        file_path: &str,
+    ) -> Result<Bot, ApiError> {
+        let spec = BotSpec::from_file(file_path)?;
+        self.create_bot(&spec).await
+    }
+
+    /// Creates a new bot from a project directory, see [`BotProject`]
+    #[cfg(feature = "fs-import")]
+    pub async fn create_bot_from_dir<P: AsRef<std::path::Path>>(&self, dir: P) -> Result<Bot, ApiError> {
+        let project = BotProject::load(dir)?;
+        self.create_bot(project.spec()).await
+    }
+
+    /// Replaces the definition of a bot, fields left unset in `spec` are not sent
+    pub async fn update_bot(&self, id: usize, spec: &BotSpec) -> Result<Bot, ApiError> {
+        self.check_spec(spec)?;
+        let data = serde_json::to_value(spec)?;
+        let response = self.send(Method::PUT, &format!("/chatbot/{}", id), Some(&data)).await?;
+
+        api::decode::<Bot>(response)
+    }
+
+    /// Replaces the definition of a bot with a project directory, see [`BotProject`]
+    #[cfg(feature = "fs-import")]
+    pub async fn update_bot_from_dir<P: AsRef<std::path::Path>>(&self, id: usize, dir: P) -> Result<Bot, ApiError> {
+        let project = BotProject::load(dir)?;
+        self.update_bot(id, project.spec()).await
+    }
+
+    /// Applies a [`BotPatch`] to the current definition of a bot and sends the merged document,
+    /// fields the patch does not touch keep their current value
+    pub async fn patch_bot(&self, id: usize, patch: &BotPatch) -> Result<Bot, ApiError> {
+        let bot = self.get_bot(id).await?;
+
+        if let Some(expected) = &patch.expected_updated_at {
+            if expected != &bot.updated_at {
+                return Err(ApiError::Conflict { expected: expected.clone(), actual: bot.updated_at });
             }
         }
 
-        pub async fn update_bot(&self, 
-            id: usize,
-            name: &str,
-            description: Option<&str>,
-            industry: Option<&str>,
-            flow: Option<HashMap<String, Value>>,
-            intents: Option<HashMap<String, Vec<String>>>,
-            webhook_url: Option<&str>,
-            webhook_trigger_intents: Option<Vec<String>>,
-            visible_on_community: Option<bool>) -> Result<Bot, ApiError> {
-
-            let url = utils::api_url(&format!("/chatbot/{}", id));
-            let mut data = HashMap::new();
-
-            data.insert("name".to_owned(), Value::String(name.to_owned()));
-    
-            if let Some(description) = description {
-                data.insert("description".to_owned(), Value::String(description.to_owned()));
-            }
-        
-            if let Some(industry) = industry {
-                data.insert("industry".to_owned(), Value::String(industry.to_owned()));
-            }
-        
-            if let Some(flow) = flow {
-                data.insert("flow".to_owned(), Value::Object(flow.into_iter().collect()));
-            }
-        
-            if let Some(intents) = intents {
-                data.insert(
-                    "intents".to_owned(),
-                    Value::Object(
-                        intents
-                            .into_iter()
-                            .map(|(k, v)| (k, Value::Array(v.into_iter().map(Value::String).collect())))
-                            .collect(),
-                    ),
-                );
-            }
-        
-            if let Some(webhook_url) = webhook_url {
-                data.insert("webhook_url".to_owned(), Value::String(webhook_url.to_owned()));
-            }
-        
-            if let Some(webhook_trigger_intents) = webhook_trigger_intents {
-                data.insert(
-                    "webhook_trigger_intents".to_owned(),
-                    Value::Array(webhook_trigger_intents.into_iter().map(Value::String).collect()),
-                );
-            }
+        let mut spec = BotSpec::export(&bot);
+        patch.apply(&mut spec);
 
-            if let Some(visible_on_community) = visible_on_community {
-                data.insert("visible_on_community".to_owned(), Value::Bool(visible_on_community));
-            }
-        
-            let response = self.client.put(&url).json(&Value::Object(data.into_iter().collect())).send().await?;
-         
-            if response.status().is_success() {
-                let result = response.json::<Bot>().await?;
-                Ok(result)
-            } else {
-                let error = response.json::<SarufiApiError>().await?;
-                Err(ApiError::GenericError(error.message()))
+        self.update_bot(id, &spec).await
+    }
+
+    /// Writes the editable definition of a bot to a JSON file and returns it.
+    ///
+    /// Server managed fields (`id`, `user_id`, timestamps, `model_name`, `evaluation_metrics`) are left
+    /// out, the file can be passed back to [`Sarufi::create_bot_from_file`].
+    #[cfg(feature = "fs-import")]
+    pub async fn export_bot<P: AsRef<std::path::Path>>(&self, id: usize, path: P) -> Result<BotSpec, ApiError> {
+        let spec = BotSpec::export(&self.get_bot(id).await?);
+        spec.to_file(path)?;
+        Ok(spec)
+    }
+
+    /// Saves the current definition of a bot in a [`SnapshotStore`]
+    #[cfg(feature = "fs-import")]
+    pub async fn snapshot_bot(&self, id: usize, store: &SnapshotStore) -> Result<Snapshot, ApiError> {
+        store.save(&self.get_bot(id).await?)
+    }
+
+    /// Pushes a snapshot back to a bot with [`Sarufi::update_bot`]
+    #[cfg(feature = "fs-import")]
+    pub async fn restore(&self, id: usize, snapshot: &Snapshot) -> Result<Bot, ApiError> {
+        self.update_bot(id, &snapshot.spec()?).await
+    }
+
+    /// Compares bot definitions with the bots of the account, see [`Plan`]
+    pub async fn plan(&self, desired: &[BotSpec], prune: bool) -> Result<Plan, ApiError> {
+        Plan::new(desired, &self.get_all_bots().await?, prune)
+    }
+
+    /// Runs the steps of a plan in order and returns the bots created or updated.
+    ///
+    /// The first failure stops the run, steps before it stay applied.
+    pub async fn apply(&self, plan: &Plan) -> Result<Vec<Bot>, ApiError> {
+        let mut bots = Vec::new();
+        for step in &plan.steps {
+            match step {
+                Step::Create { spec } => bots.push(self.create_bot(spec).await?),
+                Step::Update { id, spec, .. } => bots.push(self.update_bot(*id, spec).await?),
+                Step::Delete { id, .. } => self.delete_bot(*id).await?,
+                Step::Unchanged { .. } => {}
             }
-          
         }
+        Ok(bots)
+    }
 
-   
+    /// Makes the account match the desired bot definitions, matched to its bots by name.
+    ///
+    /// Returns the plan that was applied, or only computed with `options.dry_run`.
+    ///
+    /// ```no_run
+    /// # async fn run(api: sarufi::Sarufi) -> Result<(), sarufi::ApiError> {
+    /// use sarufi::{BotSpec, ReconcileOptions};
+    ///
+    /// let desired = vec![BotSpec::new("Pizza bot").industry("Food"), BotSpec::new("Bank bot").industry("Finance")];
+    /// let plan = api.reconcile(desired, ReconcileOptions::new().dry_run(true)).await?;
+    /// println!("{}", plan);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn reconcile(&self, desired: Vec<BotSpec>, options: ReconcileOptions) -> Result<Plan, ApiError> {
+        let plan = self.plan(&desired, options.prune).await?;
+        if !options.dry_run {
+            self.apply(&plan).await?;
+        }
+        Ok(plan)
+    }
 }
-
-    
\ No newline at end of file
@@ -0,0 +1,511 @@
+//! In-process fake of the Sarufi api, to exercise the client without network.
+//!
+//! The server keeps bots and chats in memory, answers conversations by running the
+//! flow of the bot in a [`FlowEngine`](crate::FlowEngine), and can be told to slow
+//! down or fail to test error handling.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), sarufi::ApiError> {
+//! use sarufi::mock::{Fault, MockServer};
+//!
+//! let server = MockServer::start().await?;
+//! let api = server.client()?;
+//!
+//! let reply = api.send_message(1045, "chat-1", "hello").await?;
+//! assert_eq!(reply.next_state.as_deref(), Some("end"));
+//!
+//! server.inject(Fault::status(503).path("/chatbots").times(1));
+//! assert!(api.get_all_bots().await.is_ok()); // retried after the 503
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+
+use crate::bot::Bot;
+use crate::builder::SarufiBuilder;
+use crate::errors::ApiError;
+use crate::engine::{Chat, FlowEngine};
+use crate::flow::{Flow, END_STATE};
+use crate::utils;
+use crate::Sarufi;
+
+pub use crate::engine::FALLBACK_MESSAGE;
+
+/// Bots every [`MockServer::start`] begins with, the `getResponse.json` and `postResponse.json` fixtures
+const FIXTURES: [&str; 2] = [include_str!("../getResponse.json"), include_str!("../postResponse.json")];
+
+/// A local server speaking the Sarufi api.
+///
+/// Serves `/chatbot`, `/chatbots`, `/conversation`, `/conversation/whatsapp`,
+/// `/allchannels/status` and `/conversation-state`, any bearer token is accepted.
+/// The server stops when dropped.
+pub struct MockServer {
+    base_url: String,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+/// A request received by the [`MockServer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    /// JSON body, `Null` when empty or not JSON
+    pub body: Value,
+}
+
+/// A failure the [`MockServer`] answers with instead of handling the request.
+///
+/// ```
+/// use sarufi::mock::Fault;
+///
+/// let fault = Fault::status(429).retry_after(1).path("/chatbots").times(2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub status: u16,
+    /// Response body, `{"detail": <reason>}` when not set
+    pub body: Option<String>,
+    /// Only requests to this exact path fail
+    pub path: Option<String>,
+    /// Only requests with this method fail
+    pub method: Option<String>,
+    /// Number of requests to fail, every matching request when not set
+    pub times: Option<usize>,
+    /// Seconds announced in a `Retry-After` header
+    pub retry_after: Option<u64>,
+}
+
+impl Fault {
+    pub fn status(status: u16) -> Fault {
+        Fault { status, body: None, path: None, method: None, times: None, retry_after: None }
+    }
+
+    pub fn body<S: Into<String>>(mut self, body: S) -> Fault {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn path<S: Into<String>>(mut self, path: S) -> Fault {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn method<S: Into<String>>(mut self, method: S) -> Fault {
+        self.method = Some(method.into().to_uppercase());
+        self
+    }
+
+    pub fn times(mut self, times: usize) -> Fault {
+        self.times = Some(times);
+        self
+    }
+
+    pub fn retry_after(mut self, seconds: u64) -> Fault {
+        self.retry_after = Some(seconds);
+        self
+    }
+
+    // `Option::is_none_or` needs Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.path.as_deref().map_or(true, |p| p == path) && self.method.as_deref().map_or(true, |m| m == method.as_str())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    bots: BTreeMap<usize, Value>,
+    next_id: usize,
+    chats: HashMap<(usize, String), Chat>,
+    faults: Vec<Fault>,
+    latency: Duration,
+    requests: Vec<MockRequest>,
+    /// Last handed out timestamp in microseconds, keeps `updated_at` strictly increasing
+    clock: u64,
+}
+
+impl MockServer {
+    /// Starts a server seeded with the fixture bots 1045 and 1122
+    pub async fn start() -> Result<MockServer, ApiError> {
+        let server = MockServer::empty().await?;
+        for fixture in FIXTURES {
+            server.add_bot(serde_json::from_str(fixture)?);
+        }
+        Ok(server)
+    }
+
+    /// Starts a server without any bot
+    pub async fn empty() -> Result<MockServer, ApiError> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+
+        let state = Arc::new(Mutex::new(State { next_id: 1, ..State::default() }));
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| handle(state.clone(), request))) }
+        });
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let server = Server::from_tcp(listener)
+            .map_err(|e| ApiError::GenericError(format!("{}", e)))?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                signal.await.ok();
+            });
+
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                log::error!("mock server failed: {}", e);
+            }
+        });
+
+        Ok(MockServer { base_url, state, shutdown: Some(shutdown) })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// A builder already pointing at this server
+    pub fn builder(&self) -> SarufiBuilder {
+        Sarufi::builder("mock-api-key").base_url(self.base_url.clone())
+    }
+
+    /// A client talking to this server
+    pub fn client(&self) -> Result<Sarufi, ApiError> {
+        self.builder().build()
+    }
+
+    /// Stores a bot, fields the api fills in are defaulted and a missing `id` is assigned
+    pub fn add_bot(&self, bot: Value) -> usize {
+        let mut state = self.lock();
+        let id = match bot["id"].as_u64() {
+            Some(id) => id as usize,
+            None => state.next_id,
+        };
+        state.next_id = state.next_id.max(id + 1);
+
+        let mut stored = state.new_bot(id);
+        merge(&mut stored, bot, true);
+        state.bots.insert(id, stored);
+        id
+    }
+
+    /// Current stored document of a bot
+    pub fn bot(&self, id: usize) -> Option<Value> {
+        self.lock().bots.get(&id).cloned()
+    }
+
+    pub fn bot_ids(&self) -> Vec<usize> {
+        self.lock().bots.keys().copied().collect()
+    }
+
+    /// The state a chat is waiting in, `None` for an unknown chat
+    pub fn chat_state(&self, bot_id: usize, chat_id: &str) -> Option<String> {
+        self.lock().chats.get(&(bot_id, chat_id.to_owned())).and_then(|chat| chat.state.clone())
+    }
+
+    /// Adds a failure, faults are checked in the order they were injected
+    pub fn inject(&self, fault: Fault) {
+        self.lock().faults.push(fault);
+    }
+
+    /// Removes every pending fault
+    pub fn clear_faults(&self) {
+        self.lock().faults.clear();
+    }
+
+    /// Delays every response
+    pub fn set_latency(&self, latency: Duration) {
+        self.lock().latency = latency;
+    }
+
+    /// Every request received so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle(state: Arc<Mutex<State>>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| !token.trim().is_empty());
+
+    let bytes = hyper::body::to_bytes(request.into_body()).await.unwrap_or_default();
+    let body: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+    let (latency, fault) = {
+        let mut state = state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.requests.push(MockRequest { method: method.to_string(), path: path.clone(), body: body.clone() });
+        (state.latency, state.take_fault(&method, &path))
+    };
+
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
+
+    if let Some(fault) = fault {
+        let reason = StatusCode::from_u16(fault.status).ok().and_then(|s| s.canonical_reason()).unwrap_or("Mock failure");
+        let body = fault.body.unwrap_or_else(|| json!({ "detail": reason }).to_string());
+        let mut response = reply(fault.status, body);
+        if let Some(seconds) = fault.retry_after {
+            response.headers_mut().insert(RETRY_AFTER, seconds.into());
+        }
+        return Ok(response);
+    }
+
+    if !authorized {
+        return Ok(reply(401, json!({ "detail": "Not authenticated" }).to_string()));
+    }
+
+    let (status, value) = state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).route(&method, &path, body);
+    Ok(reply(status, value.to_string()))
+}
+
+fn reply(status: u16, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+/// FastAPI style validation failure
+fn invalid(location: &[&str], message: &str) -> (u16, Value) {
+    (422, json!({ "detail": [{ "loc": location, "msg": message, "type": "value_error" }] }))
+}
+
+fn not_found(what: &str) -> (u16, Value) {
+    (404, json!({ "detail": format!("{} not found", what) }))
+}
+
+impl State {
+    fn take_fault(&mut self, method: &Method, path: &str) -> Option<Fault> {
+        let index = self.faults.iter().position(|fault| fault.matches(method, path))?;
+        let fault = self.faults[index].clone();
+
+        match &mut self.faults[index].times {
+            Some(times) if *times <= 1 => {
+                self.faults.remove(index);
+            }
+            Some(times) => *times -= 1,
+            None => {}
+        }
+        Some(fault)
+    }
+
+    fn route(&mut self, method: &Method, path: &str, body: Value) -> (u16, Value) {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+        match (method, segments.as_slice()) {
+            (&Method::GET, ["chatbots"]) => (200, Value::Array(self.bots.values().cloned().collect())),
+            (&Method::POST, ["chatbot"]) => self.create(body),
+            (&Method::GET, ["chatbot", id]) => match self.bot_id(id) {
+                Ok(id) => (200, self.bots[&id].clone()),
+                Err(e) => e,
+            },
+            (&Method::PUT, ["chatbot", id]) => match self.bot_id(id) {
+                Ok(id) => self.update(id, body),
+                Err(e) => e,
+            },
+            (&Method::DELETE, ["chatbot", id]) => match self.bot_id(id) {
+                Ok(id) => {
+                    self.bots.remove(&id);
+                    self.chats.retain(|(bot_id, _), _| *bot_id != id);
+                    (200, json!({ "message": "Bot deleted successfully" }))
+                }
+                Err(e) => e,
+            },
+            (&Method::POST, ["conversation"]) => self.converse(body, false),
+            (&Method::POST, ["conversation", "whatsapp"]) => self.converse(body, true),
+            (&Method::POST, ["allchannels", "status"]) => self.status(body),
+            (&Method::POST, ["conversation-state"]) => self.set_state(body),
+            _ => (404, json!({ "detail": "Not Found" })),
+        }
+    }
+
+    /// Id of an existing bot from a path segment
+    fn bot_id(&self, segment: &str) -> Result<usize, (u16, Value)> {
+        let id: usize = segment.parse().map_err(|_| invalid(&["path", "id"], "value is not a valid integer"))?;
+        if self.bots.contains_key(&id) {
+            Ok(id)
+        } else {
+            Err(not_found("Bot"))
+        }
+    }
+
+    /// Id of an existing bot from the `bot_id` of a request body
+    fn body_bot_id(&self, body: &Value) -> Result<usize, (u16, Value)> {
+        let id = body["bot_id"].as_u64().ok_or_else(|| invalid(&["body", "bot_id"], "field required"))? as usize;
+        if self.bots.contains_key(&id) {
+            Ok(id)
+        } else {
+            Err(not_found("Bot"))
+        }
+    }
+
+    fn new_bot(&mut self, id: usize) -> Value {
+        let now = self.timestamp();
+        json!({
+            "id": id,
+            "user_id": 1,
+            "name": "",
+            "description": "",
+            "industry": "",
+            "language": "english",
+            "intents": {},
+            "flows": {},
+            "model_name": format!("models/mock-{}.pkl", id),
+            "evaluation_metrics": null,
+            "confidence_threshold": 0.5,
+            "visible_on_community": false,
+            "webhook_url": "",
+            "webhook_trigger_intents": [],
+            "created_at": now,
+            "updated_at": now,
+        })
+    }
+
+    fn create(&mut self, body: Value) -> (u16, Value) {
+        if !body["name"].is_string() {
+            return invalid(&["body", "name"], "field required");
+        }
+
+        let id = self.next_id;
+        let mut bot = self.new_bot(id);
+        merge(&mut bot, body, false);
+        if let Err(e) = serde_json::from_value::<Bot>(bot.clone()) {
+            return invalid(&["body"], &e.to_string());
+        }
+
+        self.next_id += 1;
+        self.bots.insert(id, bot.clone());
+        (200, bot)
+    }
+
+    fn update(&mut self, id: usize, body: Value) -> (u16, Value) {
+        let mut bot = self.bots[&id].clone();
+        merge(&mut bot, body, false);
+        bot["updated_at"] = self.timestamp().into();
+        if let Err(e) = serde_json::from_value::<Bot>(bot.clone()) {
+            return invalid(&["body"], &e.to_string());
+        }
+
+        self.bots.insert(id, bot.clone());
+        (200, bot)
+    }
+
+    fn converse(&mut self, body: Value, whatsapp: bool) -> (u16, Value) {
+        let bot_id = match self.body_bot_id(&body) {
+            Ok(id) => id,
+            Err(e) => return e,
+        };
+        let chat_id = match &body["chat_id"] {
+            Value::String(chat_id) => chat_id.clone(),
+            Value::Number(chat_id) => chat_id.to_string(),
+            _ => return invalid(&["body", "chat_id"], "field required"),
+        };
+        let message = match body["message"].as_str() {
+            Some(message) => message.to_owned(),
+            None => return invalid(&["body", "message"], "field required"),
+        };
+
+        let bot = &self.bots[&bot_id];
+        let flow: Flow = serde_json::from_value(bot["flows"].clone()).unwrap_or_default();
+        let intents = serde_json::from_value(bot["intents"].clone()).unwrap_or_default();
+        let chat = self.chats.entry((bot_id, chat_id)).or_default();
+
+        (200, FlowEngine::new(intents, flow).reply(chat, &message, whatsapp))
+    }
+
+    fn status(&mut self, body: Value) -> (u16, Value) {
+        let bot_id = match self.body_bot_id(&body) {
+            Ok(id) => id,
+            Err(e) => return e,
+        };
+        let chat_id = body["chat_id"].as_str().unwrap_or_default().to_owned();
+        let chat = self.chats.get(&(bot_id, chat_id.clone()));
+
+        (200, json!({
+            "bot_id": bot_id,
+            "chat_id": chat_id,
+            "state": chat.and_then(|chat| chat.state.clone()),
+            "memory": chat.map(|chat| chat.memory.clone()).unwrap_or_default(),
+        }))
+    }
+
+    fn set_state(&mut self, body: Value) -> (u16, Value) {
+        let bot_id = match self.body_bot_id(&body) {
+            Ok(id) => id,
+            Err(e) => return e,
+        };
+        let (Some(chat_id), Some(next_state)) = (body["chat_id"].as_str(), body["next_state"].as_str()) else {
+            return invalid(&["body"], "chat_id and next_state are required");
+        };
+
+        let flow: Flow = serde_json::from_value(self.bots[&bot_id]["flows"].clone()).unwrap_or_default();
+        if next_state != END_STATE && !flow.contains_key(next_state) {
+            return (400, json!({ "detail": format!("State {:?} does not exist", next_state) }));
+        }
+
+        self.chats.entry((bot_id, chat_id.to_owned())).or_default().state = Some(next_state.to_owned());
+        (200, json!({ "message": "Conversation state updated successfully", "next_state": next_state }))
+    }
+
+    /// `2023-05-01T20:57:29.880578` style timestamp, strictly increasing across calls
+    fn timestamp(&mut self) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or_default();
+        self.clock = now.max(self.clock + 1);
+
+        let (seconds, micros) = (self.clock / 1_000_000, self.clock % 1_000_000);
+        let (year, month, day) = utils::civil_from_days((seconds / 86_400) as i64);
+        let time = seconds % 86_400;
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}",
+            year, month, day, time / 3600, time % 3600 / 60, time % 60, micros
+        )
+    }
+}
+
+/// Copies the fields of a request body onto a stored bot, `flow` is stored as `flows`.
+/// Server managed fields are only taken when `trusted`.
+fn merge(bot: &mut Value, body: Value, trusted: bool) {
+    let Value::Object(fields) = body else { return };
+
+    for (key, value) in fields {
+        let key = if key == "flow" { "flows".to_owned() } else { key };
+        let managed = matches!(key.as_str(), "id" | "user_id" | "model_name" | "created_at" | "updated_at");
+        if value.is_null() || (managed && !trusted) {
+            continue;
+        }
+        bot[key] = value;
+    }
+}
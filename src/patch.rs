@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use crate::flow::{Flow, FlowState};
+use crate::spec::BotSpec;
+
+/// A set of changes applied on top of the current definition of a bot.
+///
+/// [`Sarufi::patch_bot`](crate::Sarufi::patch_bot) fetches the bot, applies the patch
+/// and sends the merged document, so fields the patch does not mention keep their
+/// current value.
+///
+/// ```
+/// use sarufi::{BotPatch, FlowState};
+///
+/// let patch = BotPatch::new()
+///     .webhook_url("https://example.com/v2/webhook")
+///     .add_examples("greetings", ["mambo", "habari"])
+///     .upsert_state("greetings", FlowState::new(["Karibu!"]).next("end"))
+///     .remove_intent("legacy_promo");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BotPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub industry: Option<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_trigger_intents: Option<Vec<String>>,
+    pub add_trigger_intents: Vec<String>,
+    pub remove_trigger_intents: Vec<String>,
+    pub visible_on_community: Option<bool>,
+    pub language: Option<String>,
+    pub confidence_threshold: Option<f64>,
+    pub model_name: Option<String>,
+    /// Intents whose examples are replaced, or created
+    pub set_intents: HashMap<String, Vec<String>>,
+    /// Examples appended to an intent, skipping the ones already present
+    pub add_examples: HashMap<String, Vec<String>>,
+    pub remove_intents: Vec<String>,
+    pub upsert_states: HashMap<String, FlowState>,
+    pub remove_states: Vec<String>,
+    /// Refuse to update when the bot changed since this `updated_at` value
+    pub expected_updated_at: Option<String>,
+}
+
+impl BotPatch {
+    pub fn new() -> BotPatch {
+        BotPatch::default()
+    }
+
+    pub fn name<S: Into<String>>(mut self, name: S) -> BotPatch {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> BotPatch {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn industry<S: Into<String>>(mut self, industry: S) -> BotPatch {
+        self.industry = Some(industry.into());
+        self
+    }
+
+    pub fn webhook_url<S: Into<String>>(mut self, webhook_url: S) -> BotPatch {
+        self.webhook_url = Some(webhook_url.into());
+        self
+    }
+
+    /// Replaces the whole list of webhook trigger intents
+    pub fn webhook_trigger_intents<I, S>(mut self, intents: I) -> BotPatch
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.webhook_trigger_intents = Some(intents.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn add_trigger_intent<S: Into<String>>(mut self, intent: S) -> BotPatch {
+        self.add_trigger_intents.push(intent.into());
+        self
+    }
+
+    pub fn remove_trigger_intent<S: Into<String>>(mut self, intent: S) -> BotPatch {
+        self.remove_trigger_intents.push(intent.into());
+        self
+    }
+
+    pub fn visible_on_community(mut self, visible: bool) -> BotPatch {
+        self.visible_on_community = Some(visible);
+        self
+    }
+
+    pub fn language<S: Into<String>>(mut self, language: S) -> BotPatch {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn confidence_threshold(mut self, threshold: f64) -> BotPatch {
+        self.confidence_threshold = Some(threshold);
+        self
+    }
+
+    pub fn model_name<S: Into<String>>(mut self, model_name: S) -> BotPatch {
+        self.model_name = Some(model_name.into());
+        self
+    }
+
+    /// Creates an intent or replaces its examples
+    pub fn set_intent<S, I, E>(mut self, name: S, examples: I) -> BotPatch
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = E>,
+        E: Into<String>,
+    {
+        self.set_intents.insert(name.into(), examples.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Appends examples to an intent, creating it if needed
+    pub fn add_examples<S, I, E>(mut self, name: S, examples: I) -> BotPatch
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = E>,
+        E: Into<String>,
+    {
+        self.add_examples
+            .entry(name.into())
+            .or_default()
+            .extend(examples.into_iter().map(Into::into));
+        self
+    }
+
+    /// Removes an intent, it is dropped from the webhook trigger intents as well
+    pub fn remove_intent<S: Into<String>>(mut self, name: S) -> BotPatch {
+        self.remove_intents.push(name.into());
+        self
+    }
+
+    /// Adds or replaces a flow state
+    pub fn upsert_state<S: Into<String>>(mut self, name: S, state: FlowState) -> BotPatch {
+        self.upsert_states.insert(name.into(), state);
+        self
+    }
+
+    pub fn remove_state<S: Into<String>>(mut self, name: S) -> BotPatch {
+        self.remove_states.push(name.into());
+        self
+    }
+
+    /// Enables the optimistic concurrency check against the `updated_at` of the bot
+    pub fn expect_updated_at<S: Into<String>>(mut self, updated_at: S) -> BotPatch {
+        self.expected_updated_at = Some(updated_at.into());
+        self
+    }
+
+    /// Applies the changes to a bot definition in place, missing intents, trigger intents
+    /// or flow are only created when the patch adds to them
+    pub fn apply(&self, spec: &mut BotSpec) {
+        if let Some(name) = &self.name {
+            spec.name = name.clone();
+        }
+
+        set(&mut spec.description, &self.description);
+        set(&mut spec.industry, &self.industry);
+        set(&mut spec.webhook_url, &self.webhook_url);
+        set(&mut spec.webhook_trigger_intents, &self.webhook_trigger_intents);
+        set(&mut spec.visible_on_community, &self.visible_on_community);
+        set(&mut spec.language, &self.language);
+        set(&mut spec.confidence_threshold, &self.confidence_threshold);
+        set(&mut spec.model_name, &self.model_name);
+
+        if !self.set_intents.is_empty() || !self.add_examples.is_empty() {
+            let intents = spec.intents.get_or_insert_with(HashMap::new);
+            for (name, examples) in &self.set_intents {
+                intents.insert(name.clone(), examples.clone());
+            }
+            for (name, examples) in &self.add_examples {
+                let current = intents.entry(name.clone()).or_default();
+                for example in examples {
+                    if !current.contains(example) {
+                        current.push(example.clone());
+                    }
+                }
+            }
+        }
+        if let Some(intents) = &mut spec.intents {
+            for name in &self.remove_intents {
+                intents.remove(name);
+            }
+        }
+
+        if !self.add_trigger_intents.is_empty() {
+            let triggers = spec.webhook_trigger_intents.get_or_insert_with(Vec::new);
+            for intent in &self.add_trigger_intents {
+                if !triggers.contains(intent) {
+                    triggers.push(intent.clone());
+                }
+            }
+        }
+        if let Some(triggers) = &mut spec.webhook_trigger_intents {
+            triggers.retain(|intent| !self.remove_trigger_intents.contains(intent) && !self.remove_intents.contains(intent));
+        }
+
+        if !self.upsert_states.is_empty() {
+            let flow = spec.flow.get_or_insert_with(Flow::default);
+            for (name, state) in &self.upsert_states {
+                flow.insert_state(name.clone(), state.clone());
+            }
+        }
+        if let Some(flow) = &mut spec.flow {
+            for name in &self.remove_states {
+                flow.remove(name);
+            }
+        }
+    }
+}
+
+fn set<T: Clone>(field: &mut Option<T>, value: &Option<T>) {
+    if let Some(value) = value {
+        *field = Some(value.clone());
+    }
+}
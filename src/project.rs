@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use crate::errors::ApiError;
+use crate::flow::{Flow, FlowState};
+use crate::spec::BotSpec;
+
+/// File names, without extension, of the manifest at the root of a project
+const MANIFEST_NAMES: [&str; 3] = ["bot", "manifest", "metadata"];
+
+/// A bot definition split across a directory, the layout used by the Python SDK.
+///
+/// Files are read as JSON (`.json`), YAML (`.yaml`, `.yml`) or TOML (`.toml`) and
+/// merged into a single [`BotSpec`]:
+///
+/// - the manifest, `bot`, `manifest` or `metadata` at the root, holds the bot
+///   fields such as `name`, `description` or `webhook_url`
+/// - `intents` at the root and every file under `intents/` map intent names to
+///   their examples, a file holding a plain list of examples defines the intent
+///   named after the file
+/// - `flow` or `flows` at the root and every file under `flows/` map state names
+///   to flow states
+///
+/// Other files are ignored. An intent or a state defined twice is an error.
+///
+/// ```text
+/// pizza-bot/
+/// ├── bot.yaml
+/// ├── intents/
+/// │   ├── greetings.yaml    # - hello
+/// │   └── orders.json       # {"order_pizza": ["I want a pizza"]}
+/// └── flows/
+///     ├── main.yaml
+///     └── order.toml
+/// ```
+///
+/// ```no_run
+/// use sarufi::BotProject;
+///
+/// let project = BotProject::load("bots/pizza-bot")?;
+/// println!("{} intents read from {} files", project.spec().intents.as_ref().map_or(0, |i| i.len()), project.files().len());
+/// # Ok::<(), sarufi::ApiError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BotProject {
+    root: PathBuf,
+    spec: BotSpec,
+    files: Vec<PathBuf>,
+}
+
+/// What a file of a project contributes to the bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Manifest,
+    Intents,
+    Flows,
+}
+
+impl BotProject {
+    /// Reads and merges the bot definition found in `dir`
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<BotProject, ApiError> {
+        let root = dir.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(ApiError::GenericError(format!("{} is not a directory", root.display())));
+        }
+
+        let mut manifest: Option<(PathBuf, BotSpec)> = None;
+        let mut intents: HashMap<String, (Vec<String>, PathBuf)> = HashMap::new();
+        let mut states: HashMap<String, (FlowState, PathBuf)> = HashMap::new();
+        let mut files = Vec::new();
+
+        for entry in WalkDir::new(&root).sort_by_file_name() {
+            let entry = entry?;
+            let path = entry.path();
+            let Some(role) = role(&root, path) else {
+                continue;
+            };
+
+            match role {
+                Role::Manifest => {
+                    if let Some((previous, _)) = &manifest {
+                        return Err(ApiError::GenericError(format!("{} and {} are both manifests", previous.display(), path.display())));
+                    }
+                    manifest = Some((path.to_path_buf(), read(path)?));
+                }
+                Role::Intents => {
+                    let value: Value = read(path)?;
+                    let file_intents: HashMap<String, Vec<String>> = if value.is_array() {
+                        HashMap::from([(stem(path).to_owned(), parse(path, value)?)])
+                    } else {
+                        parse(path, value)?
+                    };
+                    for (name, examples) in file_intents {
+                        insert(&mut intents, "intent", name, examples, path)?;
+                    }
+                }
+                Role::Flows => {
+                    let flow: Flow = read(path)?;
+                    for (name, state) in flow.into_inner() {
+                        insert(&mut states, "state", name, state, path)?;
+                    }
+                }
+            }
+            files.push(path.to_path_buf());
+        }
+
+        let (manifest_path, mut spec) =
+            manifest.ok_or_else(|| ApiError::GenericError(format!("{} has no manifest, expected a bot, manifest or metadata file", root.display())))?;
+
+        if !intents.is_empty() {
+            let merged = spec.intents.get_or_insert_with(HashMap::new);
+            for (name, (examples, path)) in intents {
+                if merged.insert(name.clone(), examples).is_some() {
+                    return Err(defined_twice("intent", &name, &manifest_path, &path));
+                }
+            }
+        }
+
+        if !states.is_empty() {
+            let merged = spec.flow.get_or_insert_with(Flow::default);
+            for (name, (state, path)) in states {
+                if merged.insert_state(name.clone(), state).is_some() {
+                    return Err(defined_twice("state", &name, &manifest_path, &path));
+                }
+            }
+        }
+
+        Ok(BotProject { root, spec, files })
+    }
+
+    /// Whether `dir` holds a project manifest
+    pub fn is_project<P: AsRef<Path>>(dir: P) -> bool {
+        let dir = dir.as_ref();
+        MANIFEST_NAMES
+            .iter()
+            .any(|name| ["json", "yaml", "yml", "toml"].iter().any(|extension| dir.join(format!("{}.{}", name, extension)).is_file()))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The merged definition
+    pub fn spec(&self) -> &BotSpec {
+        &self.spec
+    }
+
+    pub fn into_spec(self) -> BotSpec {
+        self.spec
+    }
+
+    /// Files the definition was read from, in reading order
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+/// Role of a file from its place in the project, `None` for files to skip
+fn role(root: &Path, path: &Path) -> Option<Role> {
+    if !path.is_file() || !matches!(extension(path), "json" | "yaml" | "yml" | "toml") {
+        return None;
+    }
+
+    let relative = path.strip_prefix(root).ok()?;
+    let mut components = relative.components();
+    let first = components.next()?.as_os_str().to_str()?;
+    if components.next().is_some() {
+        return match first {
+            "intents" => Some(Role::Intents),
+            "flow" | "flows" => Some(Role::Flows),
+            _ => None,
+        };
+    }
+
+    match stem(path) {
+        name if MANIFEST_NAMES.contains(&name) => Some(Role::Manifest),
+        "intents" => Some(Role::Intents),
+        "flow" | "flows" => Some(Role::Flows),
+        _ => None,
+    }
+}
+
+/// Parses a file in the format given by its extension
+fn read<T: DeserializeOwned>(path: &Path) -> Result<T, ApiError> {
+    let text = fs::read_to_string(path)?;
+    let value: Value = match extension(path) {
+        "yaml" | "yml" => serde_yaml::from_str(&text).map_err(|e| invalid(path, e))?,
+        "toml" => toml::from_str(&text).map_err(|e| invalid(path, e))?,
+        _ => serde_json::from_str(&text).map_err(|e| invalid(path, e))?,
+    };
+    parse(path, value)
+}
+
+fn parse<T: DeserializeOwned>(path: &Path, value: Value) -> Result<T, ApiError> {
+    serde_json::from_value(value).map_err(|e| invalid(path, e))
+}
+
+fn insert<T>(entries: &mut HashMap<String, (T, PathBuf)>, kind: &str, name: String, value: T, path: &Path) -> Result<(), ApiError> {
+    if let Some((_, previous)) = entries.get(&name) {
+        return Err(defined_twice(kind, &name, previous, path));
+    }
+    entries.insert(name, (value, path.to_path_buf()));
+    Ok(())
+}
+
+fn defined_twice(kind: &str, name: &str, first: &Path, second: &Path) -> ApiError {
+    ApiError::GenericError(format!("{} {:?} is defined in both {} and {}", kind, name, first.display(), second.display()))
+}
+
+fn invalid<E: std::fmt::Display>(path: &Path, error: E) -> ApiError {
+    ApiError::GenericError(format!("{}: {}", path.display(), error))
+}
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|e| e.to_str()).unwrap_or_default()
+}
+
+fn stem(path: &Path) -> &str {
+    path.file_stem().and_then(|s| s.to_str()).unwrap_or_default()
+}
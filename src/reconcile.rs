@@ -0,0 +1,134 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::bot::Bot;
+use crate::diff::{diff_spec, BotDiff};
+use crate::errors::ApiError;
+use crate::spec::BotSpec;
+
+/// How [`Sarufi::reconcile`](crate::Sarufi::reconcile) treats the account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReconcileOptions {
+    /// Delete the bots of the account missing from the desired definitions
+    pub prune: bool,
+    /// Only compute the plan, nothing is changed
+    pub dry_run: bool,
+}
+
+/// What has to change for the account to match a set of bot definitions.
+///
+/// Definitions are matched to existing bots by name, the only key both sides share, a
+/// definition whose name several bots carry is an error.
+/// A bot is updated when a field set in its definition differs from the live bot,
+/// fields left unset are not compared, see [`diff_spec`](crate::diff_spec).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+/// One step of a [`Plan`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Step {
+    Create { spec: BotSpec },
+    Update { id: usize, spec: BotSpec, diff: BotDiff },
+    Delete { id: usize, name: String },
+    Unchanged { id: usize, name: String },
+}
+
+impl ReconcileOptions {
+    pub fn new() -> ReconcileOptions {
+        ReconcileOptions::default()
+    }
+
+    pub fn prune(mut self, prune: bool) -> ReconcileOptions {
+        self.prune = prune;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> ReconcileOptions {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+impl Plan {
+    /// Compares the desired definitions with the bots of the account, `prune` plans the
+    /// deletion of the bots no definition names
+    pub fn new(desired: &[BotSpec], current: &[Bot], prune: bool) -> Result<Plan, ApiError> {
+        let mut by_name: HashMap<&str, Vec<&Bot>> = HashMap::new();
+        for bot in current {
+            by_name.entry(bot.name.as_str()).or_default().push(bot);
+        }
+
+        let mut names = BTreeSet::new();
+        let mut steps = Vec::new();
+        for spec in desired {
+            if !names.insert(spec.name.as_str()) {
+                return Err(ApiError::GenericError(format!("bot {:?} is defined twice", spec.name)));
+            }
+
+            steps.push(match by_name.get(spec.name.as_str()).map(Vec::as_slice) {
+                None | Some([]) => Step::Create { spec: spec.clone() },
+                Some([first, second, ..]) => {
+                    return Err(ApiError::GenericError(format!("bots {} and {} are both named {:?}", first.id, second.id, spec.name)));
+                }
+                Some([bot]) => {
+                    let diff = diff_spec(bot, spec);
+                    if diff.is_empty() {
+                        Step::Unchanged { id: bot.id, name: bot.name.clone() }
+                    } else {
+                        Step::Update { id: bot.id, spec: spec.clone(), diff }
+                    }
+                }
+            });
+        }
+
+        if prune {
+            let mut removed: Vec<&Bot> = current.iter().filter(|bot| !names.contains(bot.name.as_str())).collect();
+            removed.sort_by_key(|bot| bot.id);
+            steps.extend(removed.into_iter().map(|bot| Step::Delete { id: bot.id, name: bot.name.clone() }));
+        }
+
+        Ok(Plan { steps })
+    }
+
+    /// Whether applying the plan changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.steps.iter().all(|step| matches!(step, Step::Unchanged { .. }))
+    }
+
+    /// Number of bots to create, update and delete
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.steps.iter().fold((0, 0, 0), |(create, update, delete), step| match step {
+            Step::Create { .. } => (create + 1, update, delete),
+            Step::Update { .. } => (create, update + 1, delete),
+            Step::Delete { .. } => (create, update, delete + 1),
+            Step::Unchanged { .. } => (create, update, delete),
+        })
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            match step {
+                Step::Create { spec } => writeln!(f, "+ create {:?}", spec.name)?,
+                Step::Update { id, spec, diff } => {
+                    writeln!(f, "~ update {:?} (#{})", spec.name, id)?;
+                    for line in diff.to_string().lines() {
+                        writeln!(f, "    {}", line)?;
+                    }
+                }
+                Step::Delete { id, name } => writeln!(f, "- delete {:?} (#{})", name, id)?,
+                Step::Unchanged { .. } => {}
+            }
+        }
+
+        let (create, update, delete) = self.counts();
+        let unchanged = self.steps.len() - create - update - delete;
+        write!(f, "Plan: {} to create, {} to update, {} to delete, {} unchanged", create, update, delete, unchanged)
+    }
+}
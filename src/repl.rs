@@ -0,0 +1,154 @@
+use std::io::{BufRead, Write};
+
+use serde_json::json;
+
+use crate::conversation::{ConversationResponse, Message};
+use crate::errors::ApiError;
+use crate::session::Conversation;
+use crate::whatsapp::Action;
+
+const HELP: &str = "\
+/state          show the current state of the chat
+/goto <state>   move the chat to another flow state
+/reset          start over with a new chat id
+/save <file>    write the transcript as JSON
+/help           show this help
+/quit           leave";
+
+/// Runs an interactive chat over the given conversation.
+///
+/// Every line read from `input` is sent to the bot and the reply is written to
+/// `output`, lines starting with `/` are commands (see `/help`). Api failures are
+/// reported and the session goes on, it ends on `/quit` or at the end of the input.
+///
+/// ```no_run
+/// # async fn run(api: sarufi::Sarufi) -> Result<(), sarufi::ApiError> {
+/// let mut conversation = api.conversation(1145);
+/// let stdin = std::io::stdin();
+/// sarufi::repl::run(&mut conversation, stdin.lock(), std::io::stdout()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run<R: BufRead, W: Write>(conversation: &mut Conversation<'_>, input: R, mut output: W) -> Result<(), ApiError> {
+    writeln!(output, "Chatting with bot {} (chat id {}), /help for commands", conversation.bot_id(), conversation.chat_id())?;
+    prompt(&mut output)?;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            prompt(&mut output)?;
+            continue;
+        }
+
+        match line.strip_prefix('/') {
+            Some(command) => {
+                let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+                if !handle_command(conversation, name, argument.trim(), &mut output).await? {
+                    return Ok(());
+                }
+            }
+            None => match conversation.send(line).await {
+                Ok(response) => {
+                    for text in render(&response) {
+                        writeln!(output, "{}", text)?;
+                    }
+                }
+                Err(e) => writeln!(output, "error: {}", e)?,
+            },
+        }
+
+        prompt(&mut output)?;
+    }
+
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Handles a slash command, returns `false` when the session should end
+async fn handle_command<W: Write>(conversation: &mut Conversation<'_>, name: &str, argument: &str, output: &mut W) -> Result<bool, ApiError> {
+    match name {
+        "quit" | "exit" | "q" => return Ok(false),
+        "help" | "?" => writeln!(output, "{}", HELP)?,
+        "state" => {
+            writeln!(output, "chat id: {}", conversation.chat_id())?;
+            writeln!(output, "state: {}", conversation.state().unwrap_or("(none yet)"))?;
+            match conversation.status().await {
+                Ok(status) => writeln!(output, "status: {}", status)?,
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+        }
+        "goto" if argument.is_empty() => writeln!(output, "usage: /goto <state>")?,
+        "goto" => match conversation.set_state(argument).await {
+            Ok(_) => writeln!(output, "moved to {}", argument)?,
+            Err(e) => writeln!(output, "error: {}", e)?,
+        },
+        "reset" => {
+            conversation.reset();
+            writeln!(output, "new chat id {}", conversation.chat_id())?;
+        }
+        "save" if argument.is_empty() => writeln!(output, "usage: /save <file>")?,
+        "save" => {
+            let transcript = json!({
+                "bot_id": conversation.bot_id(),
+                "chat_id": conversation.chat_id(),
+                "channel": conversation.channel(),
+                "state": conversation.state(),
+                "turns": conversation.transcript(),
+            });
+            match std::fs::write(argument, serde_json::to_string_pretty(&transcript)?) {
+                Ok(()) => writeln!(output, "saved {} turns to {}", conversation.transcript().len(), argument)?,
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+        }
+        _ => writeln!(output, "unknown command /{}, /help lists the commands", name)?,
+    }
+
+    Ok(true)
+}
+
+fn prompt<W: Write>(output: &mut W) -> Result<(), ApiError> {
+    write!(output, "> ")?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Human readable lines of a reply: texts, media links, reply buttons and list options
+pub fn render(response: &ConversationResponse) -> Vec<String> {
+    let mut lines = Vec::new();
+    for message in &response.messages {
+        match message {
+            Message::Text(text) => lines.push(text.clone()),
+            Message::Media { kind, items } => {
+                for item in items {
+                    let caption = item.caption.as_deref().map(|c| format!(" {}", c)).unwrap_or_default();
+                    let kind = format!("{:?}", kind).to_lowercase();
+                    lines.push(format!("[{}] {}{}", kind, item.link, caption));
+                }
+            }
+            Message::Other(value) => lines.push(value.to_string()),
+        }
+    }
+
+    for action in &response.actions {
+        match action {
+            Action::SendReplyButton(buttons) => {
+                lines.push(buttons.body.clone());
+                lines.extend(buttons.buttons.iter().map(|b| format!("  ({}) {}", b.id, b.title)));
+            }
+            Action::SendButton(list) => {
+                lines.push(format!("{} [{}]", list.body, list.button));
+                for section in &list.sections {
+                    if let Some(title) = &section.title {
+                        lines.push(format!("  {}", title));
+                    }
+                    lines.extend(section.rows.iter().map(|r| format!("  ({}) {}", r.id, r.title)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    lines
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use reqwest::Method;
+
+use crate::utils;
+
+/// Controls how failed requests are retried.
+///
+/// Each request is attempted up to `max_attempts` times. Between attempts the
+/// client waits for an exponentially growing delay (`initial_backoff * multiplier^n`,
+/// capped at `max_backoff`) of which a `jitter` fraction is randomized, or for the
+/// `Retry-After` delay announced by the server when `respect_retry_after` is set.
+///
+/// Only requests whose method is listed in `retry_methods` are replayed after a
+/// retryable status or a dropped connection, so a `POST /conversation` is never sent
+/// twice by default. Rate limited (429) requests and requests that failed to connect
+/// were never processed by the api and are retried for every method.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the computed backoff delay
+    pub max_backoff: Duration,
+    /// Growth factor applied to the delay after each attempt
+    pub multiplier: f64,
+    /// Fraction of the delay (between 0 and 1) that is randomized
+    pub jitter: f64,
+    /// Status codes that trigger a retry
+    pub retry_statuses: Vec<u16>,
+    /// Methods that are safe to replay
+    pub retry_methods: Vec<Method>,
+    /// Wait for the `Retry-After` delay sent with a 429 or 503 instead of the backoff
+    pub respect_retry_after: bool,
+    /// Give up instead of waiting when the server asks for a longer delay than this
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retry_statuses: vec![429, 500, 502, 503, 504],
+            retry_methods: vec![Method::GET, Method::HEAD, Method::OPTIONS, Method::PUT, Method::DELETE],
+            respect_retry_after: true,
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once
+    pub fn disabled() -> RetryPolicy {
+        RetryPolicy { max_attempts: 1, ..RetryPolicy::default() }
+    }
+
+    /// Sets the total number of attempts
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the initial and maximum backoff delays
+    pub fn with_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> RetryPolicy {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the randomized fraction of each delay, clamped between 0 and 1
+    pub fn with_jitter(mut self, jitter: f64) -> RetryPolicy {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Also replays requests sent with `method`, e.g. `POST` for endpoints known to be idempotent
+    pub fn retry_method(mut self, method: Method) -> RetryPolicy {
+        if !self.retry_methods.contains(&method) {
+            self.retry_methods.push(method);
+        }
+        self
+    }
+
+    pub(crate) fn is_idempotent(&self, method: &Method) -> bool {
+        self.retry_methods.contains(method)
+    }
+
+    /// Backoff before retry number `retry` (starting at 1), without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(retry.saturating_sub(1) as i32);
+        let delay = self.initial_backoff.as_secs_f64() * factor;
+        Duration::from_secs_f64(delay.min(self.max_backoff.as_secs_f64()))
+    }
+
+    /// Delay before retry number `retry`, `None` if the server asked to wait longer than allowed
+    pub(crate) fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(delay) if self.respect_retry_after => {
+                if delay > self.max_retry_after {
+                    None
+                } else {
+                    Some(delay)
+                }
+            }
+            _ => {
+                let delay = self.backoff(retry);
+                let jitter = delay.mul_f64(self.jitter.clamp(0.0, 1.0) * utils::random_fraction());
+                Some(delay - jitter)
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+
+use crate::conversation::{Channel, ConversationResponse};
+use crate::errors::ApiError;
+use crate::utils;
+use crate::Sarufi;
+
+/// A multi-turn chat with one bot, bound to a single chat id.
+///
+/// Every exchange is kept in a local transcript, and the state the bot moved to
+/// is tracked so callers can inspect or override it.
+///
+/// ```no_run
+/// # async fn run(api: sarufi::Sarufi) -> Result<(), sarufi::ApiError> {
+/// let mut conversation = api.conversation(1145);
+/// let reply = conversation.send("Hello").await?;
+/// println!("{}", reply.text());
+/// conversation.set_state("order_pizza").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Conversation<'a> {
+    client: &'a Sarufi,
+    bot_id: usize,
+    chat_id: String,
+    channel: Channel,
+    state: Option<String>,
+    transcript: Vec<Turn>,
+}
+
+/// One message sent by the user together with the reply of the bot
+#[derive(Debug, Clone, Serialize)]
+pub struct Turn {
+    pub message: String,
+    pub response: ConversationResponse,
+}
+
+impl<'a> Conversation<'a> {
+    /// Starts a conversation with a freshly generated chat id on the general channel
+    pub fn new(client: &'a Sarufi, bot_id: usize) -> Conversation<'a> {
+        Conversation {
+            client,
+            bot_id,
+            chat_id: utils::generate_uuid(),
+            channel: Channel::General,
+            state: None,
+            transcript: Vec::new(),
+        }
+    }
+
+    /// Continues an existing chat instead of generating a new chat id
+    pub fn with_chat_id<S: Into<String>>(mut self, chat_id: S) -> Conversation<'a> {
+        self.chat_id = chat_id.into();
+        self
+    }
+
+    /// Talks on another channel
+    pub fn with_channel<C: Into<Channel>>(mut self, channel: C) -> Conversation<'a> {
+        self.channel = channel.into();
+        self
+    }
+
+    pub fn bot_id(&self) -> usize {
+        self.bot_id
+    }
+
+    pub fn chat_id(&self) -> &str {
+        &self.chat_id
+    }
+
+    pub fn channel(&self) -> &Channel {
+        &self.channel
+    }
+
+    /// The state the bot moved to after the last reply or [`Conversation::set_state`]
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    /// Every exchange of this conversation, oldest first
+    pub fn transcript(&self) -> &[Turn] {
+        &self.transcript
+    }
+
+    /// Sends a text message and records the exchange
+    pub async fn send(&mut self, text: &str) -> Result<ConversationResponse, ApiError> {
+        let response = self
+            .client
+            .respond(self.bot_id, &self.chat_id, text, "text", self.channel.clone())
+            .await?;
+
+        if let Some(next_state) = &response.next_state {
+            self.state = Some(next_state.clone());
+        }
+
+        self.transcript.push(Turn { message: text.to_owned(), response: response.clone() });
+        Ok(response)
+    }
+
+    /// Status of this chat as reported by the api
+    pub async fn status(&self) -> Result<String, ApiError> {
+        self.client.chat_status(self.bot_id, &self.chat_id).await
+    }
+
+    /// Moves the conversation to another flow state
+    pub async fn set_state(&mut self, next_state: &str) -> Result<String, ApiError> {
+        let result = self.client.update_conversation_state(self.bot_id, &self.chat_id, next_state).await?;
+        self.state = Some(next_state.to_owned());
+        Ok(result)
+    }
+
+    /// Starts over with a new chat id and an empty transcript
+    pub fn reset(&mut self) {
+        self.chat_id = utils::generate_uuid();
+        self.state = None;
+        self.transcript.clear();
+    }
+}
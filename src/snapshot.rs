@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bot::Bot;
+use crate::errors::ApiError;
+use crate::spec::BotSpec;
+use crate::utils;
+
+/// Timestamped versions of bots kept on disk, to roll a bot back after a bad change.
+///
+/// Every bot gets a directory named after its id holding one exported definition per
+/// snapshot, `<root>/<bot id>/<timestamp>.json`. Snapshots are plain
+/// [`BotSpec`] files, so they can be committed, diffed and re-imported as they are.
+///
+/// ```no_run
+/// use sarufi::SnapshotStore;
+///
+/// # async fn run(api: sarufi::Sarufi) -> Result<(), sarufi::ApiError> {
+/// let store = SnapshotStore::open(".sarufi/snapshots")?;
+/// api.snapshot_bot(42, &store).await?;
+///
+/// // later, after an unwanted change
+/// let snapshots = store.list(42)?;
+/// api.restore(42, &snapshots[0]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+/// A saved version of a bot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub bot_id: usize,
+    /// UTC time the snapshot was taken, e.g. `2023-05-01T20-57-29.880578Z`, usable in file names
+    pub id: String,
+    pub path: PathBuf,
+}
+
+impl SnapshotStore {
+    /// Uses `root` as the store, creating it when missing
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<SnapshotStore, ApiError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        Ok(SnapshotStore { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Stores the current definition of a bot as a new snapshot
+    pub fn save(&self, bot: &Bot) -> Result<Snapshot, ApiError> {
+        let dir = self.root.join(bot.id.to_string());
+        fs::create_dir_all(&dir)?;
+
+        let mut micros = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or_default();
+        let mut id = snapshot_id(micros);
+        while dir.join(format!("{}.json", id)).exists() {
+            micros += 1;
+            id = snapshot_id(micros);
+        }
+
+        let snapshot = Snapshot { bot_id: bot.id, path: dir.join(format!("{}.json", id)), id };
+        BotSpec::export(bot).to_file(&snapshot.path)?;
+        Ok(snapshot)
+    }
+
+    /// Snapshots of a bot, oldest first
+    pub fn list(&self, bot_id: usize) -> Result<Vec<Snapshot>, ApiError> {
+        let dir = self.root.join(bot_id.to_string());
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                    snapshots.push(Snapshot { bot_id, id: id.to_owned(), path: path.clone() });
+                }
+            }
+        }
+        snapshots.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(snapshots)
+    }
+
+    /// Most recent snapshot of a bot
+    pub fn latest(&self, bot_id: usize) -> Result<Option<Snapshot>, ApiError> {
+        Ok(self.list(bot_id)?.pop())
+    }
+
+    /// A snapshot by its id
+    pub fn get(&self, bot_id: usize, id: &str) -> Result<Snapshot, ApiError> {
+        self.list(bot_id)?
+            .into_iter()
+            .find(|snapshot| snapshot.id == id)
+            .ok_or_else(|| ApiError::GenericError(format!("bot {} has no snapshot {}", bot_id, id)))
+    }
+}
+
+impl Snapshot {
+    /// The stored definition
+    pub fn spec(&self) -> Result<BotSpec, ApiError> {
+        BotSpec::from_file(&self.path)
+    }
+}
+
+/// `2023-05-01T20-57-29.880578Z`, sorting in time order
+fn snapshot_id(micros: u64) -> String {
+    let (seconds, micros) = (micros / 1_000_000, micros % 1_000_000);
+    let (year, month, day) = utils::civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}.{:06}Z",
+        year, month, day, time / 3600, time % 3600 / 60, time % 60, micros
+    )
+}
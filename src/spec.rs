@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+#[cfg(feature = "fs-import")]
+use std::fs::{self, File};
+#[cfg(feature = "fs-import")]
+use std::io::BufReader;
+#[cfg(feature = "fs-import")]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::bot::Bot;
+#[cfg(feature = "fs-import")]
+use crate::errors::ApiError;
+use crate::flow::{Flow, FlowState};
+use crate::validate::{self, Diagnostic};
+
+/// Definition of a bot as sent to the create and update endpoints.
+///
+/// Only `name` is required, every unset field is left out of the request body.
+///
+/// ```
+/// use sarufi::{BotSpec, FlowState};
+///
+/// let spec = BotSpec::new("Pizza bot")
+///     .description("Takes pizza orders")
+///     .industry("Food")
+///     .intent("greetings", ["hello", "hi"])
+///     .state("greetings", FlowState::new(["Hello, hungry?"]).next("end"))
+///     .webhook_url("https://example.com/webhook")
+///     .visible_on_community(false);
+///
+/// assert_eq!(spec.name, "Pizza bot");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BotSpec {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub industry: Option<String>,
+    /// Sent as `flow`, exported bots using `flows` are accepted too
+    #[serde(default, rename = "flow", alias = "flows", skip_serializing_if = "Option::is_none")]
+    pub flow: Option<Flow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intents: Option<HashMap<String, Vec<String>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_trigger_intents: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible_on_community: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence_threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_name: Option<String>,
+    /// Fields unknown to this SDK, sent along untouched
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl BotSpec {
+    pub fn new<S: Into<String>>(name: S) -> BotSpec {
+        BotSpec { name: name.into(), ..BotSpec::default() }
+    }
+
+    /// Reads a JSON bot definition such as `src/data/test.json`
+    #[cfg(feature = "fs-import")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<BotSpec, ApiError> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// The definition of a bot as written by exports and snapshots and sent back by patches,
+    /// [`BotSpec::from`] without the `model_name` the api assigns when training
+    pub(crate) fn export(bot: &Bot) -> BotSpec {
+        BotSpec { model_name: None, ..BotSpec::from(bot) }
+    }
+
+    /// Writes the definition as pretty JSON with sorted keys, readable back with [`BotSpec::from_file`]
+    #[cfg(feature = "fs-import")]
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ApiError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut text = serde_json::to_string_pretty(&serde_json::to_value(self)?)?;
+        text.push('\n');
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Checks the definition offline, see [`validate`](crate::validate())
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate::validate(self)
+    }
+
+    pub fn description<S: Into<String>>(mut self, description: S) -> BotSpec {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn industry<S: Into<String>>(mut self, industry: S) -> BotSpec {
+        self.industry = Some(industry.into());
+        self
+    }
+
+    /// Replaces the whole flow
+    pub fn flow(mut self, flow: Flow) -> BotSpec {
+        self.flow = Some(flow);
+        self
+    }
+
+    /// Adds or replaces a single flow state
+    pub fn state<S: Into<String>>(mut self, name: S, state: FlowState) -> BotSpec {
+        self.flow.get_or_insert_with(Flow::default).insert_state(name, state);
+        self
+    }
+
+    /// Replaces all intents
+    pub fn intents(mut self, intents: HashMap<String, Vec<String>>) -> BotSpec {
+        self.intents = Some(intents);
+        self
+    }
+
+    /// Adds or replaces a single intent with its example utterances
+    pub fn intent<S, I, E>(mut self, name: S, examples: I) -> BotSpec
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = E>,
+        E: Into<String>,
+    {
+        self.intents
+            .get_or_insert_with(HashMap::new)
+            .insert(name.into(), examples.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn webhook_url<S: Into<String>>(mut self, webhook_url: S) -> BotSpec {
+        self.webhook_url = Some(webhook_url.into());
+        self
+    }
+
+    /// Intents that trigger a call to the webhook
+    pub fn webhook_trigger_intents<I, S>(mut self, intents: I) -> BotSpec
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.webhook_trigger_intents = Some(intents.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn visible_on_community(mut self, visible: bool) -> BotSpec {
+        self.visible_on_community = Some(visible);
+        self
+    }
+
+    pub fn language<S: Into<String>>(mut self, language: S) -> BotSpec {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Minimum intent confidence before the bot falls back
+    pub fn confidence_threshold(mut self, threshold: f64) -> BotSpec {
+        self.confidence_threshold = Some(threshold);
+        self
+    }
+
+    pub fn model_name<S: Into<String>>(mut self, model_name: S) -> BotSpec {
+        self.model_name = Some(model_name.into());
+        self
+    }
+}
+
+impl From<&Bot> for BotSpec {
+    /// The editable definition of a bot, server managed fields like `id`, `user_id`,
+    /// timestamps and `evaluation_metrics` are dropped
+    fn from(bot: &Bot) -> BotSpec {
+        BotSpec {
+            name: bot.name.clone(),
+            description: Some(bot.description.clone()),
+            industry: Some(bot.industry.clone()),
+            flow: Some(bot.flows.clone()),
+            intents: Some(bot.intents.clone()),
+            webhook_url: Some(bot.webhook_url.clone()),
+            webhook_trigger_intents: Some(bot.webhook_trigger_intents.clone()),
+            visible_on_community: Some(bot.visible_on_community),
+            language: Some(bot.language.clone()),
+            confidence_threshold: bot.confidence_threshold,
+            model_name: Some(bot.model_name.clone()),
+            extra: Map::new(),
+        }
+    }
+}
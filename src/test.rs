@@ -1,15 +1,106 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use super::*;
+use mock::{Fault, MockServer};
 use std::time::Duration;
 
-use dotenv::dotenv;
-use super::*;
+/// Serves the given `(status, body)` pairs in order on a local port and
+/// returns the base url plus a handle resolving to the raw requests received
+async fn serve(responses: Vec<(u16, &'static str)>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+    serve_with_headers(responses.into_iter().map(|(status, body)| (status, "", body)).collect()).await
+}
+
+/// Like [`serve`], with extra raw header lines (each ending in `\r\n`) per response
+async fn serve_with_headers(responses: Vec<(u16, &'static str, &'static str)>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for (status, headers, body) in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 64 * 1024];
+            let mut len = 0;
+            // read until the headers and the announced body are in
+            loop {
+                let n = socket.read(&mut buf[len..]).await.unwrap();
+                len += n;
+                let text = String::from_utf8_lossy(&buf[..len]).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let content_length = text[..end]
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if len >= end + 4 + content_length || n == 0 {
+                        requests.push(text);
+                        break;
+                    }
+                }
+            }
+            let reply = format!(
+                "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n{}\r\n{}",
+                status, body.len(), headers, body
+            );
+            socket.write_all(reply.as_bytes()).await.unwrap();
+        }
+        requests
+    });
+
+    (base_url, handle)
+}
+
+#[tokio::test]
+async fn test_builder_routes_to_base_url() {
+    let (base_url, server) = serve(vec![(200, include_str!("../getResponse.json"))]).await;
+    let api = Sarufi::builder("secret")
+        .base_url(format!("{}/", base_url))
+        .header("X-Team", "ops")
+        .user_agent("sarufi-tests")
+        .build()
+        .unwrap();
+
+    assert_eq!(api.base_url(), base_url);
+
+    let bot = api.get_bot(1122).await.unwrap();
+    assert_eq!(bot.name, "My Rusty Chatbot");
+
+    let requests = server.await.unwrap();
+    let request = requests[0].to_lowercase();
+    assert!(request.starts_with("get /chatbot/1122 http/1.1"));
+    assert!(request.contains("authorization: bearer secret"));
+    assert!(request.contains("x-team: ops"));
+    assert!(request.contains("user-agent: sarufi-tests"));
+}
+
+#[tokio::test]
+async fn test_builder_custom_client_keeps_auth() {
+    let (base_url, server) = serve(vec![(200, "[]")]).await;
+    let api = Sarufi::builder("secret")
+        .base_url(base_url)
+        .client(reqwest::Client::new())
+        .build()
+        .unwrap();
+
+    assert!(api.get_all_bots().await.unwrap().is_empty());
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].to_lowercase().contains("authorization: bearer secret"));
+}
+
+#[test]
+fn test_builder_rejects_bad_config() {
+    assert!(Sarufi::builder("").build().is_err());
+    assert!(Sarufi::builder("key").base_url("not a url").build().is_err());
+    assert!(Sarufi::builder("key").header("bad header", "value").build().is_err());
+    assert_eq!(Sarufi::new("key").unwrap().base_url(), "https://developers.sarufi.io");
+}
 
 
 #[tokio::test]
 async fn test_get_bot() {
-    dotenv().ok();
-    let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-    let api = Sarufi::new(api_key).unwrap();
-    let bot = api.get_bot(1205).await.unwrap();
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
+    let bot = api.get_bot(1045).await.unwrap();
     
     
     println!("Name: {:?}", bot.name);
@@ -17,70 +108,54 @@ async fn test_get_bot() {
     println!("Description: {:?}", bot.description);
     println!("Industry: {:?}", bot.industry);
     
-
+    assert_eq!(bot.name, "My Rusty Chatbot");
+    assert!(matches!(api.get_bot(1).await, Err(ApiError::NotFound { .. })));
 }
 
 #[tokio::test]
 async fn test_get_all_bot() {
-    dotenv().ok();
-    let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-    let api = Sarufi::new(api_key).unwrap();
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
     let bots = api.get_all_bots().await.unwrap();
 
-    // assert_eq!(bots[0].id, "My Rust Chatbot");
-
     println!("Result: {:?}", bots.len());
-    
+    assert_eq!(bots.iter().map(|b| b.id).collect::<Vec<_>>(), vec![1045, 1122]);
 }
 
-// #[tokio::test]
-// async fn test_delete_all_bots() {
-//     dotenv().ok();
-//     let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-//     let api = Sarufi::new(api_key).unwrap();
-//     let bots = api.get_all_bots().await.unwrap();
+#[tokio::test]
+async fn test_delete_all_bots() {
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
+    let bots = api.get_all_bots().await.unwrap();
     
-//     for bot in bots {
-//         api.delete_bot(bot.id).await.unwrap();
-//         println!("Deleted bot {}", bot.id);
-//         tokio::time::sleep(Duration::from_secs(1)).await; // Delay for one second
-//     }
-// }
+    for bot in bots {
+        api.delete_bot(bot.id).await.unwrap();
+        println!("Deleted bot {}", bot.id);
+    }
+    assert!(api.get_all_bots().await.unwrap().is_empty());
+}
 
 #[tokio::test]
 async fn test_create_bot() -> Result<(), ApiError> {
-    dotenv().ok();
-    let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-    // println!("API_KEY: {:?}", api_key);
-    let api = Sarufi::new(api_key).unwrap();
-
-    let name = "My Rusty Chatbot";
-    let description = Some("A rusty chatbot created using Sarufi API");
-    let industry = Some("Technology");
-    let flow: Option<HashMap<String, Value>> = None;
-    let intents: Option<HashMap<String, Vec<String>>> = None;
-    let webhook_url = Some("https://example.com/webhook");
-    let webhook_trigger_intents: Option<Vec<String>> = None;
-    let visible_on_community = Some(true);  
-
-    let bot = api.create_bot(
-            name,
-            description,
-            industry,
-            flow,
-            intents,
-            webhook_url,
-            webhook_trigger_intents,
-            visible_on_community,
-    ).await?;  
+    let server = MockServer::empty().await?;
+    let api = server.client()?;
+
+    let spec = BotSpec::new("My Rusty Chatbot")
+        .description("A rusty chatbot created using Sarufi API")
+        .industry("Technology")
+        .webhook_url("https://example.com/webhook")
+        .visible_on_community(true);
+
+    let bot = api.create_bot(&spec).await?;
 
     println!("Result: {:?}", bot);
     println!("ID: {:?}", bot.id);
 
 
-    assert_eq!(bot.name, name);
-    assert_eq!(bot.description, description.unwrap());
-    assert_eq!(bot.industry, industry.unwrap());
+    assert_eq!(bot.name, spec.name);
+    assert_eq!(Some(bot.description), spec.description);
+    assert_eq!(Some(bot.industry), spec.industry);
+    assert_eq!(server.bot_ids(), vec![bot.id]);
 
     Ok(())
 
@@ -89,53 +164,38 @@ async fn test_create_bot() -> Result<(), ApiError> {
 
 #[tokio::test]
 async fn test_update_bot() {
-    dotenv().ok();
-    let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-    // println!("API_KEY: {:?}", api_key);
-    let api = Sarufi::new(api_key).unwrap();
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
 
-    let id = 1112; // change this to your bot id
+    let id = 1122;
 
     let prev_bot = api.get_bot(id).await.unwrap();
-    
-        
-    let name = "My Other Rusty Chatbot";
-    let description = Some("A rusty chatbot created using Sarufi API");
-    let industry = Some("Technology");
-    let flow: Option<HashMap<String, Value>> = None;
-    let intents: Option<HashMap<String, Vec<String>>> = None;
-    let webhook_url = Some("https://example.com/webhook");
-    let webhook_trigger_intents: Option<Vec<String>> = None;
-    let visible_on_community = Some(true);  
-
-    let bot = api.update_bot(
-            id,
-            name,
-            description,
-            industry,
-            flow,
-            intents,
-            webhook_url,
-            webhook_trigger_intents,
-            visible_on_community,
-    ).await.unwrap();
+    println!("Previous name: {:?}", prev_bot.name);
 
-    println!("Result: {:?}", bot.id);
+    let spec = BotSpec::new("My Other Rusty Chatbot")
+        .description("A rusty chatbot created using Sarufi API")
+        .industry("Technology")
+        .webhook_url("https://example.com/webhook")
+        .visible_on_community(true);
+
+    let bot = api.update_bot(id, &spec).await.unwrap();
 
-    assert_eq!(bot.name, name);
-    assert_eq!(bot.description, description.unwrap());
-    assert_eq!(bot.industry, industry.unwrap());
+    println!("Result: {:?}", bot.id);
 
+    assert_eq!(bot.name, spec.name);
+    assert_eq!(Some(bot.description), spec.description);
+    assert_eq!(Some(bot.industry), spec.industry);
+    assert_ne!(bot.updated_at, prev_bot.updated_at);
+    assert_eq!(bot.intents, prev_bot.intents);
 }
 
 #[tokio::test]
 async fn test_fetch () {
-    dotenv().ok();
-    let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-    let api = Sarufi::new(api_key).unwrap();
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
 
 
-    let bot_id = 1145; // change this to your bot id
+    let bot_id = 1045;
     let chat_id = "123456789";
     let message = "Hello";
     let message_type = "text";
@@ -143,26 +203,1057 @@ async fn test_fetch () {
 
     let response = api._fetch_response(bot_id, chat_id, message, message_type, channel).await.unwrap();
     println!("Result: {:?}", response);
-
+    assert_eq!(response, r#"["Hello, Your Welcome"]"#);
 }
 
 #[tokio::test]
 async fn test_chat () {
-    // Import the required types and traits
-    dotenv().ok();
-    let api_key = std::env::var("SARUFI_API_KEY").expect("API_KEY env required to run test");
-    let api = Sarufi::new(api_key).unwrap();
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
 
-    let bot_id = 1145; // change this to your bot id
+    let bot_id = 1045;
     let response = api.chat(bot_id).await.unwrap();
     println!("Result: {:?}", response.as_str());
+    assert!(response.contains("Hello, Your Welcome"));
+}
+
+#[tokio::test]
+async fn test_mock_walks_choice_flows() {
+    let server = MockServer::empty().await.unwrap();
+    let bot_id = server.add_bot(serde_json::json!({
+        "name": "Pizza",
+        "intents": { "order_pizza": ["I want pizza", "pizza"] },
+        "flows": {
+            "order_pizza": { "message": ["What pizza? 1. Margherita 2. Pepperoni"], "next_state": "choose_pizza" },
+            "choose_pizza": { "1": "margherita", "2": "pepperoni", "fallback_message": ["Pick 1 or 2"] },
+            "margherita": { "message": ["One Margherita coming"], "next_state": "end" },
+            "pepperoni": { "message": ["One Pepperoni coming"], "next_state": "end" }
+        }
+    }));
+    let api = server.client().unwrap();
+
+    let mut conversation = api.conversation(bot_id).with_chat_id("chat-1");
+    assert_eq!(conversation.send("Can I get a pizza?").await.unwrap().next_state.as_deref(), Some("choose_pizza"));
+    assert_eq!(conversation.send("3").await.unwrap().text(), "Pick 1 or 2");
+    let reply = conversation.send("2").await.unwrap();
+    assert_eq!(reply.text(), "One Pepperoni coming");
+    assert!(reply.is_end());
+    assert_eq!(reply.memory["choose_pizza"], "2");
+    assert_eq!(conversation.send("weather?").await.unwrap().text(), engine::FALLBACK_MESSAGE);
+
+    conversation.set_state("choose_pizza").await.unwrap();
+    assert_eq!(server.chat_state(bot_id, "chat-1").as_deref(), Some("choose_pizza"));
+    assert!(matches!(conversation.set_state("missing").await, Err(ApiError::Validation { status: 400, .. })));
+
+    let reply = conversation.with_channel(Channel::WhatsApp).send("1").await.unwrap();
+    assert_eq!(reply.raw["actions"][0]["send_message"][0], "One Margherita coming");
+    assert_eq!(reply.text(), "One Margherita coming");
+}
+
+#[tokio::test]
+async fn test_mock_fault_injection() {
+    let server = MockServer::start().await.unwrap();
+    let api = server.builder().retry_policy(fast_retries()).build().unwrap();
+
+    server.inject(Fault::status(503).path("/chatbots").times(2));
+    assert_eq!(api.get_all_bots().await.unwrap().len(), 2);
+
+    server.inject(Fault::status(429).retry_after(0).method("post"));
+    assert!(matches!(api.create_bot(&BotSpec::new("x")).await, Err(ApiError::RateLimited { .. })));
+    server.clear_faults();
+
+    server.inject(Fault::status(422).body(r#"{"detail": [{"loc": ["body", "name"], "msg": "too short", "type": "value_error"}]}"#).times(1));
+    match api.get_bot(1045).await {
+        Err(ApiError::Validation { details, .. }) => assert_eq!(details[0].message, "too short"),
+        other => panic!("unexpected result {:?}", other.map(|b| b.id)),
+    }
+
+    server.set_latency(Duration::from_millis(300));
+    let slow = server.builder().timeout(Duration::from_millis(50)).no_retries().build().unwrap();
+    assert!(matches!(slow.get_bot(1045).await, Err(ApiError::Transport(_))));
+
+    server.set_latency(Duration::ZERO);
+    let anonymous = reqwest::get(format!("{}/chatbots", server.base_url())).await.unwrap();
+    assert_eq!(anonymous.status(), 401);
+
+    let requests = server.requests();
+    assert_eq!(requests.iter().filter(|r| r.path == "/chatbots").count(), 4);
+    assert_eq!(requests[3].method, "POST");
+    assert_eq!(requests[3].body["name"], "x");
+}
+
+#[test]
+fn test_error_for_status_kinds() {
+    let err = api::error_for_status(401, None, r#"{"error": "Invalid token"}"#.to_owned());
+    assert!(matches!(err, ApiError::Unauthorized { status: 401, ref message, .. } if message == "Invalid token"));
+
+    let err = api::error_for_status(429, Some(std::time::Duration::from_secs(3)), String::new());
+    assert!(matches!(err, ApiError::RateLimited { retry_after: Some(d), ref message, .. } if d.as_secs() == 3 && message == "Too Many Requests"));
+
+    let body = r#"{"detail": [{"loc": ["body", "name"], "msg": "field required", "type": "value_error.missing"}]}"#;
+    match api::error_for_status(422, None, body.to_owned()) {
+        ApiError::Validation { details, message, body: raw, .. } => {
+            assert_eq!(message, "field required");
+            assert_eq!(details, vec![FieldError {
+                location: vec!["body".to_owned(), "name".to_owned()],
+                message: "field required".to_owned(),
+                kind: Some("value_error.missing".to_owned()),
+            }]);
+            assert_eq!(raw, body);
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    let err = api::error_for_status(502, None, "<html>Bad gateway</html>".to_owned());
+    assert!(matches!(err, ApiError::Server { status: 502, .. }));
+    assert_eq!(err.body(), Some("<html>Bad gateway</html>"));
+    assert_eq!(api::error_for_status(409, None, String::new()).status(), Some(409));
+}
+
+#[tokio::test]
+async fn test_not_found_with_foreign_body() {
+    let (base_url, _server) = serve(vec![(404, "not here")]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    match api.get_bot(1).await {
+        Err(ApiError::NotFound { status, message, body }) => {
+            assert_eq!(status, 404);
+            assert_eq!(message, "not here");
+            assert_eq!(body, "not here");
+        }
+        other => panic!("unexpected result {:?}", other.map(|b| b.id)),
+    }
+}
+
+#[tokio::test]
+async fn test_decode_error_keeps_body() {
+    let (base_url, _server) = serve(vec![(200, r#"{"unexpected": true}"#)]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let err = api.get_bot(1).await.err().unwrap();
+    assert!(matches!(err, ApiError::Decode { .. }));
+    assert_eq!(err.body(), Some(r#"{"unexpected": true}"#));
+}
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy::default()
+        .with_max_attempts(3)
+        .with_backoff(std::time::Duration::from_millis(1), std::time::Duration::from_millis(5))
+}
+
+#[tokio::test]
+async fn test_retries_transient_failures() {
+    let (base_url, server) = serve_with_headers(vec![
+        (503, "", r#"{"error": "busy"}"#),
+        (429, "retry-after: 0\r\n", r#"{"error": "slow down"}"#),
+        (200, "", "[]"),
+    ]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).retry_policy(fast_retries()).build().unwrap();
+
+    assert!(api.get_all_bots().await.unwrap().is_empty());
+    assert_eq!(server.await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_retries_give_up_after_max_attempts() {
+    let (base_url, server) = serve(vec![(500, "oops"), (500, "oops")]).await;
+    let api = Sarufi::builder("secret")
+        .base_url(base_url)
+        .retry_policy(fast_retries().with_max_attempts(2))
+        .build()
+        .unwrap();
+
+    let err = api.get_bot(1).await.err().unwrap();
+    assert!(matches!(err, ApiError::Server { status: 500, .. }));
+    assert_eq!(server.await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_post_not_replayed_on_server_error() {
+    let (base_url, server) = serve(vec![(503, "busy")]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).retry_policy(fast_retries()).build().unwrap();
+
+    let err = api._fetch_response(1, "chat", "Hello", "text", "general").await.err().unwrap();
+    assert_eq!(err.status(), Some(503));
+    assert_eq!(server.await.unwrap().len(), 1);
+}
+
+#[test]
+fn test_retry_backoff_curve() {
+    let policy = RetryPolicy::default()
+        .with_backoff(std::time::Duration::from_millis(100), std::time::Duration::from_millis(350))
+        .with_jitter(0.0);
+
+    assert_eq!(policy.backoff(1).as_millis(), 100);
+    assert_eq!(policy.backoff(2).as_millis(), 200);
+    assert_eq!(policy.backoff(3).as_millis(), 350);
+
+    let jittered = policy.clone().with_jitter(0.5).delay(2, None).unwrap();
+    assert!(jittered.as_millis() >= 100 && jittered.as_millis() <= 200);
+
+    let retry_after = std::time::Duration::from_secs(120);
+    assert_eq!(policy.delay(1, Some(retry_after)), None);
+    assert_eq!(policy.delay(1, Some(std::time::Duration::from_secs(2))), Some(std::time::Duration::from_secs(2)));
+}
+
+#[test]
+fn test_conversation_response_parsing() {
+    let raw = serde_json::json!({
+        "message": [
+            ["Hello, Your Welcome"],
+            "What would you like?",
+            {"send_images": [{"link": "https://example.com/menu.png", "caption": "Menu"}]},
+            {"send_button": {"body": "Pick one"}}
+        ],
+        "memory": {"greetings": "hello"},
+        "next_state": "choose_pizza"
+    });
+
+    let response = ConversationResponse::from_value(raw.clone());
+
+    assert_eq!(response.messages.len(), 4);
+    assert_eq!(response.text(), "Hello, Your Welcome\nWhat would you like?");
+    assert_eq!(response.messages[2], Message::Media {
+        kind: MediaKind::Image,
+        items: vec![Media { link: "https://example.com/menu.png".to_owned(), caption: Some("Menu".to_owned()) }],
+    });
+    assert!(matches!(response.messages[3], Message::Other(_)));
+    assert_eq!(response.next_state.as_deref(), Some("choose_pizza"));
+    assert_eq!(response.memory["greetings"], "hello");
+    assert!(!response.is_end());
+    assert_eq!(response.raw, raw);
+}
+
+#[tokio::test]
+async fn test_send_message_returns_all_messages() {
+    let (base_url, server) = serve(vec![(200, r#"{"message": ["Hi", "How can I help?"], "next_state": "end"}"#)]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let response = api.send_message(1145, "chat-1", "Hello").await.unwrap();
+    assert_eq!(response.messages, vec![Message::Text("Hi".to_owned()), Message::Text("How can I help?".to_owned())]);
+    assert!(response.is_end());
+
+    let request = server.await.unwrap().remove(0);
+    assert!(request.starts_with("POST /conversation HTTP/1.1"));
+    assert!(request.contains(r#""chat_id":"chat-1""#));
+}
+
+#[tokio::test]
+async fn test_whatsapp_channel_uses_whatsapp_endpoint() {
+    let body = r#"{
+        "actions": [
+            {"send_message": ["Welcome to Pizza Shop"]},
+            {"send_reply_button": {"type": "button", "body": {"text": "Ready to order?"}, "action": {"buttons": [
+                {"type": "reply", "reply": {"id": "yes", "title": "Yes"}},
+                {"type": "reply", "reply": {"id": "no", "title": "No"}}
+            ]}}},
+            {"send_button": {"header": "Menu", "body": "Pick a pizza", "action": {"button": "Pizzas", "sections": [
+                {"title": "Classics", "rows": [{"id": 1, "title": "Margherita", "description": "Tomato and basil"}]}
+            ]}}},
+            {"send_images": [{"link": "https://example.com/pizza.png"}]},
+            {"send_location": {"latitude": 1.0}}
+        ],
+        "next_state": "choose_pizza"
+    }"#;
+    let (base_url, server) = serve(vec![(200, body)]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let response = api.respond(1145, "255700000000", "Hi", "text", Channel::WhatsApp).await.unwrap();
+
+    let request = server.await.unwrap().remove(0);
+    assert!(request.starts_with("POST /conversation/whatsapp HTTP/1.1"));
+    assert!(request.contains(r#""channel":"whatsapp""#));
+
+    use whatsapp::{Action, Button, Row};
+    assert_eq!(response.actions.len(), 5);
+    assert_eq!(response.actions[0], Action::SendMessage(vec!["Welcome to Pizza Shop".to_owned()]));
+    match &response.actions[1] {
+        Action::SendReplyButton(buttons) => {
+            assert_eq!(buttons.body, "Ready to order?");
+            assert_eq!(buttons.buttons[1], Button { id: "no".to_owned(), title: "No".to_owned() });
+        }
+        other => panic!("unexpected action {:?}", other),
+    }
+    match &response.actions[2] {
+        Action::SendButton(list) => {
+            assert_eq!(list.button, "Pizzas");
+            assert_eq!(list.header.as_deref(), Some("Menu"));
+            assert_eq!(list.sections[0].rows[0], Row {
+                id: "1".to_owned(),
+                title: "Margherita".to_owned(),
+                description: Some("Tomato and basil".to_owned()),
+            });
+        }
+        other => panic!("unexpected action {:?}", other),
+    }
+    assert!(matches!(response.actions[3], Action::SendMedia { kind: MediaKind::Image, .. }));
+    assert!(matches!(response.actions[4], Action::Other(_)));
+
+    assert_eq!(response.text(), "Welcome to Pizza Shop");
+    assert_eq!(response.messages.len(), 2);
+}
+
+#[test]
+fn test_channel_names() {
+    assert_eq!(Channel::from("WhatsApp"), Channel::WhatsApp);
+    assert_eq!(Channel::from("other"), Channel::Other("other".to_owned()));
+    assert_eq!(Channel::from("other").endpoint(), "/conversation");
+    assert_eq!(serde_json::to_value(Channel::General).unwrap(), "general");
+}
+
+#[tokio::test]
+async fn test_conversation_keeps_chat_id_and_transcript() {
+    let (base_url, server) = serve(vec![
+        (200, r#"{"message": ["What pizza?"], "next_state": "choose_pizza"}"#),
+        (200, r#"{"message": ["Great choice"], "next_state": "end"}"#),
+        (200, r#"{"status": "active"}"#),
+        (200, r#"{"message": "state updated"}"#),
+    ]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let mut conversation = api.conversation(1145).with_chat_id("chat-7");
+    conversation.send("I want pizza").await.unwrap();
+    assert_eq!(conversation.state(), Some("choose_pizza"));
+    conversation.send("Margherita").await.unwrap();
+    assert_eq!(conversation.state(), Some("end"));
+
+    assert!(conversation.status().await.unwrap().contains("active"));
+    conversation.set_state("order_pizza").await.unwrap();
+    assert_eq!(conversation.state(), Some("order_pizza"));
+
+    let transcript = conversation.transcript();
+    assert_eq!(transcript.len(), 2);
+    assert_eq!(transcript[1].message, "Margherita");
+    assert_eq!(transcript[1].response.text(), "Great choice");
+
+    let requests = server.await.unwrap();
+    assert!(requests.iter().all(|r| r.contains(r#""chat_id":"chat-7""#)));
+    assert!(requests[2].starts_with("POST /allchannels/status"));
+    assert!(requests[3].starts_with("POST /conversation-state") && requests[3].contains(r#""next_state":"order_pizza""#));
+
+    conversation.reset();
+    assert_ne!(conversation.chat_id(), "chat-7");
+    assert!(conversation.transcript().is_empty());
+}
+
+#[test]
+fn test_flow_round_trip_keeps_unknown_fields() {
+    let flows = serde_json::json!({
+        "greetings": {"message": [["Hello"], ["Hi"]], "next_state": "end"},
+        "order_pizza": {
+            "message": ["What pizza?", "1. Margherita", "2. Pepperoni", {"send_images": [{"link": "https://example.com/menu.png"}]}],
+            "next_state": "choose_pizza"
+        },
+        "choose_pizza": {
+            "1": "confirm",
+            "2": "confirm",
+            "fallback_message": ["Please pick 1 or 2"],
+            "webhook": {"enabled": true},
+            "description": "Pizza menu"
+        },
+        "confirm": {"message": "Done!", "next_state": "end"}
+    });
+
+    let flow: Flow = serde_json::from_value(flows.clone()).unwrap();
+
+    let choose = &flow["choose_pizza"];
+    assert!(choose.is_choice());
+    assert_eq!(choose.transitions(), vec!["confirm", "confirm"]);
+    assert_eq!(MessageBlock::texts_of(&choose.fallback_message), vec!["Please pick 1 or 2"]);
+    assert_eq!(choose.extra["webhook"], serde_json::json!({"enabled": true}));
+    // unknown string fields are not choices
+    assert_eq!(choose.choices.len(), 2);
+    assert_eq!(choose.extra["description"], "Pizza menu");
+    assert_eq!(flow["greetings"].texts(), vec!["Hello", "Hi"]);
+    assert!(matches!(flow["order_pizza"].message[3], MessageBlock::Structured(_)));
+    assert_eq!(flow.state_names(), vec!["choose_pizza", "confirm", "greetings", "order_pizza"]);
+
+    let mut expected = flows;
+    expected["confirm"]["message"] = serde_json::json!(["Done!"]);
+    assert_eq!(serde_json::to_value(&flow).unwrap(), expected);
+}
+
+#[test]
+fn test_bot_fixtures_parse_typed_flows() {
+    let bot: Bot = serde_json::from_str(include_str!("../getResponse.json")).unwrap();
+    assert_eq!(bot.flows["bye"].next_state.as_deref(), Some(END_STATE));
+
+    let data: Value = serde_json::from_str(include_str!("data/test.json")).unwrap();
+    let flow: Flow = serde_json::from_value(data["flow"].clone()).unwrap();
+    assert_eq!(serde_json::to_value(&flow).unwrap(), data["flow"]);
+
+    let built = Flow::new()
+        .with_state("greetings", FlowState::new(["Hello"]).next("choose"))
+        .with_state("choose", FlowState::default().choice("1", "end").fallback(["1?"]));
+    assert_eq!(serde_json::to_value(&built).unwrap(), serde_json::json!({
+        "greetings": {"message": ["Hello"], "next_state": "choose"},
+        "choose": {"1": "end", "fallback_message": ["1?"]}
+    }));
+}
+
+#[tokio::test]
+async fn test_create_and_update_send_spec() {
+    let fixture = include_str!("../postResponse.json");
+    let (base_url, server) = serve(vec![(200, fixture), (200, fixture)]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let spec = BotSpec::new("My Rusty Chatbot")
+        .description("A rusty chatbot created using Sarufi API")
+        .intent("greetings", ["hello", "hi"])
+        .state("greetings", FlowState::new(["Hello"]).next("end"))
+        .webhook_trigger_intents(["greetings"])
+        .confidence_threshold(0.5);
+
+    let bot = api.create_bot(&spec).await.unwrap();
+    assert_eq!(bot.id, 1122);
+    api.update_bot(bot.id, &BotSpec::new("Renamed")).await.unwrap();
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("POST /chatbot HTTP/1.1"));
+    let body: Value = serde_json::from_str(requests[0].split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert_eq!(body, serde_json::json!({
+        "name": "My Rusty Chatbot",
+        "description": "A rusty chatbot created using Sarufi API",
+        "intents": {"greetings": ["hello", "hi"]},
+        "flow": {"greetings": {"message": ["Hello"], "next_state": "end"}},
+        "webhook_trigger_intents": ["greetings"],
+        "confidence_threshold": 0.5
+    }));
+
+    assert!(requests[1].starts_with("PUT /chatbot/1122 HTTP/1.1"));
+    assert!(requests[1].ends_with(r#"{"name":"Renamed"}"#));
+}
 
+#[cfg(feature = "fs-import")]
+#[test]
+fn test_bot_spec_from_file() {
+    let spec = BotSpec::from_file("src/data/test.json").unwrap();
+    assert_eq!(spec.name, "My Test Bot");
+    assert_eq!(spec.flow.as_ref().unwrap()["bye"].texts(), vec!["Goodbye", "See you later"]);
+    assert_eq!(spec.intents.as_ref().unwrap()["greetings"].len(), 3);
+    assert_eq!(spec.visible_on_community, Some(true));
 
+    let exported: BotSpec = serde_json::from_str(r#"{"name": "x", "flows": {}, "custom": 1}"#).unwrap();
+    assert_eq!(exported.flow, Some(Flow::new()));
+    assert_eq!(serde_json::to_value(&exported).unwrap(), serde_json::json!({"name": "x", "flow": {}, "custom": 1}));
 }
 
+#[cfg(feature = "fs-import")]
+#[tokio::test]
+async fn test_bot_project_from_dir() {
+    let project = BotProject::load("src/data/pizza_project").unwrap();
+    assert_eq!(project.files().len(), 5);
+    let spec = project.spec();
+    assert_eq!(spec.name, "Pizza bot");
+    assert_eq!(spec.webhook_trigger_intents, Some(vec!["order_pizza".to_owned()]));
+
+    let intents = spec.intents.as_ref().unwrap();
+    let mut names: Vec<&str> = intents.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["goodbye", "greetings", "order_pizza"]);
+    assert_eq!(intents["greetings"], ["hello", "hi", "good morning"]);
+
+    let flow = spec.flow.as_ref().unwrap();
+    assert_eq!(flow.state_names(), ["goodbye", "greetings", "large_pizza", "order_pizza", "small_pizza"]);
+    assert_eq!(flow["order_pizza"].choices["2"], "large_pizza");
+    assert!(!has_errors(&spec.validate()));
+
+    let server = MockServer::empty().await.unwrap();
+    let api = server.client().unwrap();
+    let bot = api.create_bot_from_dir("src/data/pizza_project").await.unwrap();
+    assert_eq!(api.send_message(bot.id, "c1", "pizza please").await.unwrap().text(), "Which size do you want?\n1. Small\n2. Large");
+    assert_eq!(api.update_bot_from_dir(bot.id, "src/data/pizza_project").await.unwrap().intents.len(), 3);
+
+    let dir = std::env::temp_dir().join(format!("sarufi-project-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(dir.join("intents")).unwrap();
+    std::fs::write(dir.join("bot.json"), r#"{"name": "Twice", "intents": {"hello": ["hi"]}}"#).unwrap();
+    std::fs::write(dir.join("intents/hello.yml"), "- hey\n").unwrap();
+    let error = BotProject::load(&dir).unwrap_err().to_string();
+    assert!(error.contains("\"hello\" is defined in both"), "{}", error);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_patch_bot_merges_with_current_definition() {
+    let fixture = include_str!("../getResponse.json");
+    let (base_url, server) = serve(vec![(200, fixture), (200, fixture)]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let patch = BotPatch::new()
+        .webhook_url("https://example.com/v2/webhook")
+        .add_examples("greetings", ["hello", "mambo"])
+        .remove_intent("thanks")
+        .upsert_state("greetings", FlowState::new(["Karibu!"]).next("end"))
+        .remove_state("thanks")
+        .add_trigger_intent("bye");
+    api.patch_bot(1122, &patch).await.unwrap();
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("GET /chatbot/1122"));
+    assert!(requests[1].starts_with("PUT /chatbot/1122"));
+    let sent: Value = serde_json::from_str(requests[1].split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    let current: Value = serde_json::from_str(fixture).unwrap();
+
+    assert_eq!(sent["name"], current["name"]);
+    assert_eq!(sent["description"], current["description"]);
+    assert_eq!(sent["webhook_url"], "https://example.com/v2/webhook");
+    assert_eq!(sent["flow"]["bye"], current["flows"]["bye"]);
+    assert_eq!(sent["flow"]["greetings"], serde_json::json!({"message": ["Karibu!"], "next_state": "end"}));
+    assert!(sent["flow"].get("thanks").is_none());
+    assert!(sent["intents"].get("thanks").is_none());
+    assert_eq!(sent["intents"]["bye"], current["intents"]["bye"]);
+
+    let greetings = sent["intents"]["greetings"].as_array().unwrap();
+    assert_eq!(greetings.len(), current["intents"]["greetings"].as_array().unwrap().len() + 1);
+    assert_eq!(greetings.last().unwrap(), "mambo");
+    assert!(sent["webhook_trigger_intents"].as_array().unwrap().contains(&Value::from("bye")));
+    assert!(sent.get("id").is_none() && sent.get("evaluation_metrics").is_none());
+    assert!(sent.get("model_name").is_none());
+}
+
+#[test]
+fn test_bot_patch_leaves_missing_sections_out() {
+    let mut spec = BotSpec::new("Pizza bot");
+    BotPatch::new().webhook_url("https://example.com/webhook").remove_intent("thanks").remove_state("thanks").apply(&mut spec);
+    assert_eq!(spec.webhook_url.as_deref(), Some("https://example.com/webhook"));
+    assert!(spec.intents.is_none() && spec.webhook_trigger_intents.is_none() && spec.flow.is_none());
+
+    BotPatch::new().add_examples("greetings", ["hello"]).add_trigger_intent("greetings").apply(&mut spec);
+    assert_eq!(spec.intents.as_ref().unwrap()["greetings"], ["hello"]);
+    assert_eq!(spec.webhook_trigger_intents.as_deref(), Some(&["greetings".to_owned()][..]));
+}
+
+#[tokio::test]
+async fn test_patch_bot_detects_concurrent_change() {
+    let (base_url, server) = serve(vec![(200, include_str!("../getResponse.json"))]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).build().unwrap();
+
+    let patch = BotPatch::new().description("new").expect_updated_at("2023-01-01T00:00:00");
+    let err = api.patch_bot(1122, &patch).await.err().unwrap();
+
+    assert!(matches!(err, ApiError::Conflict { ref expected, .. } if expected == "2023-01-01T00:00:00"));
+    assert_eq!(server.await.unwrap().len(), 1);
+}
+
+#[test]
+fn test_validate_reports_broken_definitions() {
+    #[cfg(feature = "fs-import")]
+    assert!(validate_file("src/data/test.json").unwrap().iter().all(|d| d.severity != Severity::Error));
+
+    let spec = BotSpec::new("Pizza bot")
+        .intent("greetings", ["hello"])
+        .intent("order", ["I want pizza", "Hello"])
+        .intent("empty", Vec::<String>::new())
+        .state("greetings", FlowState::new(["Hi!"]).next("end"))
+        .state("order", FlowState::new(["Which size?"]).next("choose size"))
+        .state("choose size", FlowState::default().choice("1", "small").choice("2", "missing"))
+        .state("small", FlowState::new(["Done"]).next("nowhere"))
+        .state("orphan", FlowState::new(["Nobody comes here"]).next("end"))
+        .webhook_trigger_intents(["order", "unknown"]);
+
+    let diagnostics = validate(&spec);
+    let found: Vec<(Severity, &str, &str)> = diagnostics.iter().map(|d| (d.severity, d.code, d.path.as_str())).collect();
+
+    assert_eq!(found, vec![
+        (Severity::Warning, "unreachable-state", "$.flow.orphan"),
+        (Severity::Error, "unknown-state", "$.flow.small.next_state"),
+        (Severity::Warning, "missing-fallback", r#"$.flow["choose size"]"#),
+        (Severity::Error, "unknown-state", r#"$.flow["choose size"]["2"]"#),
+        (Severity::Error, "empty-intent", "$.intents.empty"),
+        (Severity::Error, "intent-without-flow", "$.intents.empty"),
+        (Severity::Warning, "duplicate-example", "$.intents.order[1]"),
+        (Severity::Error, "unknown-trigger-intent", "$.webhook_trigger_intents[1]"),
+        (Severity::Warning, "missing-webhook-url", "$.webhook_url"),
+    ]);
+    assert!(has_errors(&diagnostics));
+}
+
+#[tokio::test]
+async fn test_validation_before_create_is_opt_in() {
+    let api = Sarufi::builder("secret").base_url("http://127.0.0.1:9").validate_bots(true).build().unwrap();
+    let spec = BotSpec::new("Broken").intent("greetings", ["hi"]);
+
+    match api.create_bot(&spec).await {
+        Err(ApiError::InvalidSpec(diagnostics)) => assert_eq!(diagnostics[0].code, "intent-without-flow"),
+        other => panic!("unexpected result {:?}", other.map(|b| b.id)),
+    }
+}
+
+#[tokio::test]
+async fn test_repl_session() {
+    let (base_url, server) = serve(vec![
+        (200, r#"{"message": ["Karibu!", "What pizza?"], "next_state": "choose_pizza"}"#),
+        (200, r#"{"message": "state updated"}"#),
+        (500, "down"),
+    ]).await;
+    let api = Sarufi::builder("secret").base_url(base_url).no_retries().build().unwrap();
+    let transcript = std::env::temp_dir().join(format!("sarufi-repl-{}.json", utils::generate_uuid()));
+
+    let input = format!("hello\n\n/goto order_pizza\nmargherita\n/save {}\n/nope\n/quit\nnever sent\n", transcript.display());
+    let mut output = Vec::new();
+    let mut conversation = api.conversation(1145).with_chat_id("chat-9");
+    repl::run(&mut conversation, input.as_bytes(), &mut output).await.unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("Karibu!\nWhat pizza?\n"));
+    assert!(output.contains("moved to order_pizza"));
+    assert!(output.contains("error: Server error (500)"));
+    assert!(output.contains("unknown command /nope"));
+    assert_eq!(conversation.state(), Some("order_pizza"));
+
+    let saved: Value = serde_json::from_str(&std::fs::read_to_string(&transcript).unwrap()).unwrap();
+    std::fs::remove_file(&transcript).ok();
+    assert_eq!(saved["chat_id"], "chat-9");
+    assert_eq!(saved["turns"].as_array().unwrap().len(), 1);
+    assert_eq!(saved["turns"][0]["message"], "hello");
+
+    let requests = server.await.unwrap();
+    assert!(requests[1].starts_with("POST /conversation-state"));
+    assert!(requests[2].contains(r#""message":"margherita""#));
+}
+
+#[tokio::test]
+async fn test_cassette_records_and_replays() {
+    let path = std::env::temp_dir().join(format!("sarufi-cassette-{}.jsonl", utils::generate_uuid()));
+    let server = MockServer::start().await.unwrap();
+    server.inject(Fault::status(503).path("/chatbot/1045").times(1));
 
+    let recording = server.builder().retry_policy(fast_retries()).cassette(Cassette::record(&path).unwrap()).build().unwrap();
+    let bot = recording.get_bot(1045).await.unwrap();
+    let reply = recording.send_message(1045, "chat-1", "hello").await.unwrap();
+    assert!(matches!(recording.get_bot(1).await, Err(ApiError::NotFound { .. })));
+    assert_eq!(recording.cassette().unwrap().interactions().len(), 4);
+    drop(server);
 
+    let replaying = Sarufi::builder("unused")
+        .base_url("http://127.0.0.1:9")
+        .retry_policy(fast_retries())
+        .cassette(Cassette::replay(&path).unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(replaying.get_bot(1045).await.unwrap().updated_at, bot.updated_at);
+    assert_eq!(replaying.send_message(1045, "chat-1", "hello").await.unwrap().raw, reply.raw);
+    assert!(matches!(replaying.get_bot(1).await, Err(ApiError::NotFound { .. })));
+    assert!(replaying.cassette().unwrap().unused().is_empty());
 
+    // the body is part of the match, and every interaction answers once
+    assert!(matches!(replaying.send_message(1045, "chat-2", "hello").await, Err(ApiError::Transport(_))));
+    assert!(matches!(replaying.get_bot(1045).await, Err(ApiError::Transport(_))));
+    std::fs::remove_file(&path).ok();
+}
+
+#[tokio::test]
+async fn test_cassette_replay_snapshot() {
+    let api = Sarufi::builder("unused")
+        .base_url("http://127.0.0.1:9")
+        .cassette(Cassette::replay("src/data/cassettes/conversation.jsonl").unwrap())
+        .build()
+        .unwrap();
+
+    let bot = api.get_bot(1045).await.unwrap();
+    insta::assert_json_snapshot!("cassette_bot_flows", serde_json::to_value(&bot.flows).unwrap());
 
+    let mut conversation = api.conversation(1045).with_chat_id("cassette-chat");
+    conversation.send("Hello").await.unwrap();
+    conversation.send("thanks").await.unwrap();
+    insta::assert_json_snapshot!("cassette_transcript", serde_json::to_value(conversation.transcript()).unwrap());
+    insta::assert_snapshot!("cassette_status", conversation.status().await.unwrap());
+}
+
+/// Answers from a script and keeps the requests, without any socket
+struct ScriptedTransport {
+    replies: std::sync::Mutex<Vec<Result<HttpResponse, TransportError>>>,
+    requests: std::sync::Mutex<Vec<HttpRequest>>,
+}
 
+#[async_trait::async_trait]
+impl Transport for std::sync::Arc<ScriptedTransport> {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        self.requests.lock().unwrap().push(request);
+        self.replies.lock().unwrap().remove(0)
+    }
+}
 
+#[tokio::test]
+async fn test_custom_transport_gets_prepared_requests() {
+    let transport = std::sync::Arc::new(ScriptedTransport {
+        replies: std::sync::Mutex::new(vec![
+            Err(TransportError::new(TransportErrorKind::Connect, "connection refused")),
+            Ok(HttpResponse::new(200, include_str!("../postResponse.json"))),
+            Err(TransportError::new(TransportErrorKind::Timeout, "timed out")),
+            Ok(HttpResponse::new(200, "not json")),
+        ]),
+        requests: std::sync::Mutex::new(Vec::new()),
+    });
+    let api = Sarufi::builder("secret")
+        .base_url("https://sarufi.example")
+        .retry_policy(fast_retries())
+        .transport(transport.clone())
+        .build()
+        .unwrap();
+
+    let bot = api.create_bot(&BotSpec::new("My Rusty Chatbot")).await.unwrap();
+    assert_eq!(bot.id, 1122);
+    // a timeout may have reached the server, so the POST is not replayed
+    assert!(matches!(api.send_message(1122, "chat-1", "hi").await, Err(ApiError::Transport(m)) if m == "timed out"));
+    assert!(matches!(api.get_bot(1122).await, Err(ApiError::Decode { body, .. }) if body == "not json"));
+
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[1].url, "https://sarufi.example/chatbot");
+    assert_eq!(requests[1].headers["authorization"], "Bearer secret");
+    assert_eq!(requests[1].json().unwrap()["name"], "My Rusty Chatbot");
+    assert_eq!((requests[3].method.as_str(), requests[3].path.as_str(), requests[3].body.is_none()), ("GET", "/chatbot/1122", true));
+
+    let shared = api.clone();
+    assert_eq!(shared.base_url(), api.base_url());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_blocking_client() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(MockServer::start()).unwrap();
+    let api = blocking::Sarufi::from_builder(server.builder()).unwrap();
+
+    let bot = api.create_bot(&BotSpec::new("Sync bot").intent("greetings", ["hello"]).state("greetings", FlowState::new(["Hi there"]).next("end"))).unwrap();
+    assert_eq!(api.get_bot(bot.id).unwrap().name, "Sync bot");
+    assert_eq!(api.get_all_bots().unwrap().len(), 3);
+    assert_eq!(api.patch_bot(bot.id, &BotPatch::new().description("patched")).unwrap().description, "patched");
+
+    let mut conversation = api.conversation(bot.id).with_chat_id("sync-chat");
+    assert_eq!(conversation.send("hello").unwrap().text(), "Hi there");
+    assert_eq!(conversation.state(), Some("end"));
+    conversation.set_state("greetings").unwrap();
+    assert!(conversation.status().unwrap().contains("greetings"));
+    assert_eq!(conversation.transcript().len(), 1);
+
+    api.delete_bot(bot.id).unwrap();
+    assert!(matches!(api.get_bot(bot.id), Err(ApiError::NotFound { .. })));
+}
+
+#[cfg(feature = "env-config")]
+#[test]
+fn test_client_from_env() {
+    std::env::set_var("SARUFI_API_KEY", "from-env");
+    std::env::set_var("SARUFI_BASE_URL", "http://127.0.0.1:8080/");
+
+    let api = Sarufi::from_env().unwrap();
+    assert_eq!(api.base_url(), "http://127.0.0.1:8080");
+}
+
+#[cfg(feature = "webhook")]
+#[tokio::test]
+async fn test_webhook_dispatches_by_intent() {
+    use webhook::{Webhook, WebhookPayload, WebhookReply};
+
+    let webhook = Webhook::new()
+        .on("order_pizza", |payload: WebhookPayload| async move {
+            let size = payload.memory_str("pizza_size").unwrap_or("medium").to_owned();
+            Ok(WebhookReply::text(format!("One {} pizza for {}", size, payload.chat_id)).next_state("end").memory("order_id", 7))
+        })
+        .on("broken", |_| async { Err(ApiError::GenericError("kitchen closed".into())) });
+    assert_eq!(webhook.intents(), ["broken", "order_pizza"]);
+
+    let server = webhook.clone().bind(([127, 0, 0, 1], 0).into()).await.unwrap();
+    let client = reqwest::Client::new();
+    let call = |body: serde_json::Value| client.post(server.url()).body(body.to_string()).send();
+
+    let response = call(serde_json::json!({
+        "intent": "order_pizza",
+        "chat_id": 255700000000u64,
+        "bot_id": 1045,
+        "message": "large",
+        "memory": { "pizza_size": "large" },
+        "prev_state": "choose_size",
+    }))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 200);
+    let reply: WebhookReply = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(reply, WebhookReply::text("One large pizza for 255700000000").next_state("end").memory("order_id", 7));
+
+    let response = call(serde_json::json!({ "intent": "unknown", "chat_id": "c" })).await.unwrap();
+    assert_eq!(response.status(), 404);
+    let response = call(serde_json::json!({ "intent": "broken", "chat_id": "c" })).await.unwrap();
+    assert_eq!(response.status(), 500);
+    assert!(response.text().await.unwrap().contains("kitchen closed"));
+    let response = client.post(server.url()).body("not json").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+    assert_eq!(client.get(server.url()).send().await.unwrap().status(), 405);
+
+    let webhook = webhook.fallback(|payload: WebhookPayload| async move { Ok(WebhookReply::text(format!("no idea about {}", payload.intent))) });
+    let (status, body) = webhook.handle(br#"{"intent": "unknown", "chat_id": "c"}"#).await;
+    assert_eq!((status, body), (200, serde_json::json!({ "message": ["no idea about unknown"] })));
+}
+
+#[cfg(feature = "webhook")]
+#[tokio::test]
+async fn test_webhook_rejects_large_bodies() {
+    use webhook::{Webhook, WebhookReply, DEFAULT_MAX_BODY_SIZE};
+
+    let webhook = Webhook::new().on("greetings", |_| async { Ok(WebhookReply::text("hi")) });
+    assert!(format!("{:?}", webhook).contains(&DEFAULT_MAX_BODY_SIZE.to_string()));
+    let server = webhook.max_body_size(64).bind(([127, 0, 0, 1], 0).into()).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let small = serde_json::json!({ "intent": "greetings", "chat_id": "c" }).to_string();
+    assert_eq!(client.post(server.url()).body(small.clone()).send().await.unwrap().status(), 200);
+
+    // refused from the Content-Length alone
+    let large = serde_json::json!({ "intent": "greetings", "chat_id": "c", "message": "x".repeat(100) }).to_string();
+    let response = client.post(server.url()).body(large.clone()).send().await.unwrap();
+    assert_eq!(response.status(), 413);
+    assert!(response.text().await.unwrap().contains("larger than 64 bytes"));
+
+    // chunked bodies carry no length and are counted while read
+    let mut stream = tokio::net::TcpStream::connect(server.local_addr()).await.unwrap();
+    let chunks: String = large.as_bytes().chunks(16).map(|chunk| format!("{:x}\r\n{}\r\n", chunk.len(), std::str::from_utf8(chunk).unwrap())).collect();
+    let request = format!("POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{}0\r\n\r\n", chunks);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+}
+
+#[cfg(feature = "webhook")]
+#[tokio::test]
+async fn test_router_verifies_and_routes_intents() {
+    use webhook::{IntentContext, IntentHandler, Router, WebhookReply};
+
+    struct Balance;
+
+    #[async_trait]
+    impl IntentHandler for Balance {
+        async fn handle(&self, context: IntentContext) -> Result<WebhookReply, ApiError> {
+            Ok(WebhookReply::text(format!("Account {} holds 100", context.slot_str("account").unwrap_or("?"))))
+        }
+    }
+
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
+    let spec = BotSpec::new("Bank bot")
+        .intent("balance", ["balance"])
+        .intent("transfer", ["send money"])
+        .state("balance", FlowState::new(["Which account?"]).next("end"))
+        .state("transfer", FlowState::new(["How much?"]).next("confirm"))
+        .state("confirm", FlowState::new(["Confirmed"]).next("end"))
+        .webhook_trigger_intents(["balance", "transfer"]);
+    let bot = api.create_bot(&spec).await.unwrap();
+
+    let router = Router::new(api.clone()).route("balance", Balance);
+    assert_eq!(router.missing(&bot), ["transfer"]);
+    assert!(router.verify(&bot).unwrap_err().to_string().contains("transfer"));
+
+    let router = router.bot_id(bot.id).route("transfer", |context: IntentContext| async move {
+        context.set_state("confirm").await?;
+        Ok(WebhookReply::text(format!("Sending for chat {}", context.chat_id())))
+    });
+    router.verify(&bot).unwrap();
+    assert_eq!(router.intents(), ["balance", "transfer"]);
+
+    let webhook = router.into_webhook();
+    let (status, body) = webhook.handle(br#"{"intent": "balance", "chat_id": "c1", "memory": {"account": "42"}}"#).await;
+    assert_eq!((status, body), (200, serde_json::json!({ "message": ["Account 42 holds 100"] })));
+
+    let (status, body) = webhook.handle(br#"{"intent": "transfer", "chat_id": "c2"}"#).await;
+    assert_eq!((status, body), (200, serde_json::json!({ "message": ["Sending for chat c2"] })));
+    assert_eq!(server.chat_state(bot.id, "c2").as_deref(), Some("confirm"));
+
+    assert_eq!(webhook.handle(br#"{"intent": "greetings", "chat_id": "c3"}"#).await.0, 404);
+}
+
+#[tokio::test]
+async fn test_reconcile_plans_and_applies() {
+    let server = MockServer::empty().await.unwrap();
+    let api = server.client().unwrap();
+    api.create_bot(&BotSpec::new("Pizza bot").description("Takes orders")).await.unwrap();
+    api.create_bot(&BotSpec::new("Bank bot").description("Answers questions").intent("greetings", ["hello"])).await.unwrap();
+    let current = api.get_all_bots().await.unwrap();
+    let (kept, changed) = (&current[0], &current[1]);
+
+    let desired = vec![
+        BotSpec::new(kept.name.clone()).description(kept.description.clone()),
+        BotSpec::new(changed.name.clone()).description("Rewritten").intent("goodbye", ["bye"]),
+        BotSpec::new("Brand new bot").industry("Retail"),
+    ];
+
+    let plan = Plan::new(&desired, &current, false).unwrap();
+    assert_eq!(plan.counts(), (1, 1, 0));
+    assert!(matches!(&plan.steps[0], Step::Unchanged { id, .. } if *id == kept.id));
+    let Step::Update { id, diff, .. } = &plan.steps[1] else { panic!("expected an update, got {:?}", plan.steps[1]) };
+    assert_eq!(*id, changed.id);
+    let changes: Vec<String> = diff.changes.iter().map(ToString::to_string).collect();
+    assert_eq!(changes, ["~ description: \"Answers questions\" -> \"Rewritten\"", "+ intents.goodbye: [\"bye\"]", "- intents.greetings: [\"hello\"]"]);
+
+    let text = plan.to_string();
+    assert!(text.contains("+ create \"Brand new bot\""), "{}", text);
+    assert!(text.ends_with("Plan: 1 to create, 1 to update, 0 to delete, 1 unchanged"), "{}", text);
+
+    let dry_run = api.reconcile(desired.clone(), ReconcileOptions::new().dry_run(true).prune(true)).await.unwrap();
+    assert_eq!(dry_run.counts(), (1, 1, 0));
+    assert_eq!(server.bot_ids().len(), 2);
+
+    let applied = api.reconcile(desired[1..].to_vec(), ReconcileOptions::new().prune(true)).await.unwrap();
+    assert_eq!(applied.counts(), (1, 1, 1));
+    assert!(server.bot(kept.id).is_none());
+    assert_eq!(server.bot(changed.id).unwrap()["description"], "Rewritten");
+
+    assert!(api.plan(&desired[1..], true).await.unwrap().is_empty());
+    assert!(Plan::new(&[BotSpec::new("a"), BotSpec::new("a")], &[], false).is_err());
+
+    // bots sharing a name only matter when a definition uses that name
+    let twin = || serde_json::from_str::<Bot>(include_str!("../getResponse.json")).unwrap();
+    let mut twins = vec![twin(), twin()];
+    twins[1].id += 1;
+    assert_eq!(Plan::new(&desired[2..], &twins, false).unwrap().counts(), (1, 0, 0));
+    assert_eq!(Plan::new(&desired[2..], &twins, true).unwrap().counts(), (1, 0, 2));
+    let err = Plan::new(&[BotSpec::new(twins[0].name.clone())], &twins, false).err().unwrap();
+    assert!(err.to_string().contains("both named"), "{}", err);
+}
+
+#[test]
+fn test_diff_bots() {
+    let old: Bot = serde_json::from_str(include_str!("../getResponse.json")).unwrap();
+    let mut new: Bot = serde_json::from_str(include_str!("../getResponse.json")).unwrap();
+    assert!(diff(&old, &new).is_empty());
+
+    new.description = "Orders pizza".to_owned();
+    new.webhook_url = "https://example.com/v2/webhook".to_owned();
+    new.webhook_trigger_intents.push("order_pizza".to_owned());
+    new.intents.get_mut("greetings").unwrap().push("mambo".to_owned());
+    new.intents.insert("goodbye".to_owned(), vec!["bye".to_owned()]);
+    new.flows.insert_state("goodbye", FlowState::new(["Bye!"]).next("end"));
+    let greetings = new.flows.get_mut("greetings").unwrap();
+    greetings.next_state = Some("goodbye".to_owned());
+
+    let diff = diff(&old, &new);
+    assert_eq!(diff.section(Section::Webhook).count(), 2);
+    assert_eq!(diff.section(Section::Flow).map(|c| c.path.as_str()).collect::<Vec<_>>(), ["flow.goodbye", "flow.greetings.next_state"]);
+
+    insta::assert_snapshot!("diff_text", diff.to_string());
+    insta::assert_json_snapshot!("diff_json", diff.to_json());
+    insta::assert_snapshot!("diff_unified", diff.unified());
+
+    // only the fields set in a spec are compared
+    let spec = BotSpec::new(old.name.clone()).description("Orders pizza");
+    let partial = diff_spec(&old, &spec);
+    assert_eq!(partial.changes.len(), 1);
+    assert_eq!(partial.changes[0].kind, ChangeKind::Changed);
+    assert!(diff_spec(&old, &BotSpec::from(&old)).unified().is_empty());
+
+    // thousands of changed lines stay cheap, the changed block is replaced as a whole
+    let intents = |prefix: &str| (0..3000).map(|i| (format!("{}{:04}", prefix, i), vec![format!("example {}", i)])).collect();
+    let large = BotSpec::new("Large bot").intents(intents("a"));
+    let patch = diff_specs(&large, &BotSpec::new("Large bot").intents(intents("b"))).unified();
+    assert_eq!(patch.lines().filter(|line| line.starts_with("@@")).count(), 1);
+    assert!(patch.contains("\n-    \"a0000\": [\n") && patch.contains("\n-    \"a2999\": [\n"));
+    assert!(patch.contains("\n+    \"b0000\": [\n") && patch.contains("\n+    \"b2999\": [\n"));
+}
+
+#[test]
+fn test_flow_engine() {
+    let spec = BotSpec::new("Pizza bot")
+        .intent("greetings", ["hello", "hi"])
+        .intent("order_pizza", ["I want a pizza"])
+        .state("greetings", FlowState::new(["Hello!", "How can I help?"]).next("end"))
+        .state("order_pizza", FlowState::new(["Which size?"]).choice("1", "small").choice("2", "large").fallback(["Pick 1 or 2"]))
+        .state("small", FlowState::new(["Your address?"]).next("address"))
+        .state("large", FlowState::new(["Large is sold out"]).next("end"))
+        .state("address", FlowState::new(["On its way"]).next("end"));
+    let mut engine = FlowEngine::from_spec(&spec);
+
+    assert_eq!(engine.classify("HELLO!"), Some("greetings"));
+    assert_eq!(engine.classify("well, I want a pizza now"), Some("order_pizza"));
+    let reply = engine.send("chat-1", "hi");
+    assert_eq!(reply.text(), "Hello!\nHow can I help?");
+    assert!(reply.is_end());
+
+    // a choice state repeats until a valid option comes
+    assert_eq!(engine.send("chat-1", "I want a pizza").next_state.as_deref(), Some("order_pizza"));
+    assert_eq!(engine.send("chat-1", "3").text(), "Pick 1 or 2");
+    assert_eq!(engine.state("chat-1"), Some("order_pizza"));
+    assert_eq!(engine.send("chat-1", "1.").next_state.as_deref(), Some("address"));
+    let reply = engine.send("chat-1", "Mikocheni");
+    assert_eq!(reply.text(), "On its way");
+    assert_eq!(reply.memory["order_pizza"], "1.");
+    assert_eq!(reply.memory["address"], "Mikocheni");
+
+    // chats are independent
+    assert_eq!(engine.state("chat-2"), None);
+    assert_eq!(engine.send("chat-2", "weather?").text(), engine::FALLBACK_MESSAGE);
+
+    assert!(engine.set_state("chat-2", "missing").is_err());
+    engine.set_state("chat-2", "order_pizza").unwrap();
+    let reply = engine.send_whatsapp("chat-2", "2");
+    assert_eq!(reply.raw["actions"], serde_json::json!([{ "send_message": ["Large is sold out"] }]));
+    assert_eq!(reply.text(), "Large is sold out");
+
+    // a choice may end the chat directly
+    let mut ending = FlowEngine::from_spec(&spec.clone().state("order_pizza", FlowState::new(["Which size?"]).choice("1", "small").choice("2", "end")));
+    ending.send("chat-3", "I want a pizza");
+    let reply = ending.send("chat-3", "2");
+    assert!(reply.is_end());
+    assert!(reply.messages.is_empty());
+    assert_eq!(ending.state("chat-3"), Some("end"));
+
+    engine.reset("chat-2");
+    assert_eq!(engine.memory("chat-2"), None);
+    let mut engine = engine.fallback(["Say hello"]);
+    assert_eq!(engine.send("chat-2", "weather?").text(), "Say hello");
+}
+
+#[test]
+fn test_flow_graph() {
+    let spec = BotSpec::new("Pizza \"Express\"")
+        .intent("order_pizza", ["I want a pizza"])
+        .intent("greetings", ["hello"])
+        .intent("complaints", ["too late"])
+        .state("greetings", FlowState::new(["Hello!"]).next("end"))
+        .state("order_pizza", FlowState::new(["Which size?"]).choice("1", "small").choice("2", "large"))
+        .state("small", FlowState::new(["Small it is"]).next("payment"))
+        .state("large", FlowState::new(["Large it is"]).next("end"));
+
+    let graph = FlowGraph::from_spec(&spec);
+    // no state for complaints, payment is never defined
+    assert_eq!(graph.edges.iter().filter(|e| e.kind == EdgeKind::Trigger).count(), 2);
+    let missing: Vec<_> = graph.nodes.iter().filter(|n| n.kind == NodeKind::Missing).map(|n| n.label.as_str()).collect();
+    assert_eq!(missing, ["payment"]);
+    assert_eq!(graph.nodes.last().unwrap().kind, NodeKind::End);
+
+    insta::assert_snapshot!("graph_dot", graph.to_dot());
+    insta::assert_snapshot!("graph_mermaid", graph.to_mermaid());
+
+    let bot: Bot = serde_json::from_str(include_str!("../getResponse.json")).unwrap();
+    assert_eq!(FlowGraph::from_bot(&bot), FlowGraph::from_spec(&BotSpec::from(&bot)));
+}
+
+#[cfg(feature = "fs-import")]
+#[tokio::test]
+async fn test_export_snapshot_and_restore() {
+    let server = MockServer::start().await.unwrap();
+    let api = server.client().unwrap();
+    let dir = std::env::temp_dir().join(format!("sarufi-snapshots-{}", uuid::Uuid::new_v4()));
+
+    let exported = api.export_bot(1045, dir.join("export/bot.json")).await.unwrap();
+    let file: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.join("export/bot.json")).unwrap()).unwrap();
+    for field in ["id", "user_id", "created_at", "updated_at", "model_name", "evaluation_metrics"] {
+        assert!(file.get(field).is_none(), "{} was exported", field);
+    }
+    let copy = api.create_bot_from_file(dir.join("export/bot.json").to_str().unwrap()).await.unwrap();
+    assert!(diff_spec(&copy, &exported).is_empty(), "{}", diff_spec(&copy, &exported));
+
+    let store = SnapshotStore::open(dir.join("snapshots")).unwrap();
+    let first = api.snapshot_bot(1045, &store).await.unwrap();
+    api.patch_bot(1045, &BotPatch::new().description("broken by a bad change")).await.unwrap();
+    let second = api.snapshot_bot(1045, &store).await.unwrap();
+
+    assert_eq!(store.list(1045).unwrap(), [first.clone(), second.clone()]);
+    assert_eq!(store.latest(1045).unwrap(), Some(second));
+    assert!(store.list(1122).unwrap().is_empty());
+    assert!(store.get(1045, "nope").is_err());
+
+    let restored = api.restore(1045, &store.get(1045, &first.id).unwrap()).await.unwrap();
+    assert_eq!(restored.description, exported.description.unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method};
+
+use crate::errors::ApiError;
+
+/// A fully prepared api request: url resolved, auth and content headers set, body serialized
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    /// Endpoint path the url was built from, e.g. `/chatbots`
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+}
+
+/// The raw answer of a [`Transport`], any status code
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// What went wrong while no response could be obtained, drives the retry decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportErrorKind {
+    /// The connection could not be established, always safe to retry
+    Connect,
+    Timeout,
+    /// The request failed while being sent
+    Request,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportError {
+    pub kind: TransportErrorKind,
+    pub message: String,
+}
+
+/// Sends requests on behalf of [`Sarufi`](crate::Sarufi).
+///
+/// The client prepares every request and interprets every response itself, a transport
+/// only moves bytes. Implement it to add middleware, use another HTTP stack or fake
+/// the api in unit tests, then hand it to
+/// [`SarufiBuilder::transport`](crate::SarufiBuilder::transport). The trait is
+/// declared with [`async_trait`](crate::async_trait), re-exported by this crate.
+///
+/// ```
+/// use sarufi::{async_trait, HttpRequest, HttpResponse, Transport, TransportError};
+///
+/// struct AlwaysEmpty;
+///
+/// #[async_trait]
+/// impl Transport for AlwaysEmpty {
+///     async fn send(&self, _request: HttpRequest) -> Result<HttpResponse, TransportError> {
+///         Ok(HttpResponse::new(200, "[]"))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError>;
+}
+
+/// The default [`Transport`], backed by a `reqwest::Client`
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl HttpRequest {
+    /// The body parsed as JSON, `None` when there is no body or it is not JSON
+    pub fn json(&self) -> Option<serde_json::Value> {
+        self.body.as_deref().and_then(|body| serde_json::from_slice(body).ok())
+    }
+}
+
+impl HttpResponse {
+    pub fn new<B: Into<Vec<u8>>>(status: u16, body: B) -> HttpResponse {
+        HttpResponse { status, headers: HeaderMap::new(), body: body.into() }
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Value of a header, `None` when missing or not valid text
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// The body as text, invalid UTF-8 is replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+impl TransportError {
+    pub fn new<S: Into<String>>(kind: TransportErrorKind, message: S) -> TransportError {
+        TransportError { kind, message: message.into() }
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<TransportError> for ApiError {
+    fn from(error: TransportError) -> ApiError {
+        ApiError::Transport(error.message)
+    }
+}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(error: reqwest::Error) -> TransportError {
+        let kind = if error.is_connect() {
+            TransportErrorKind::Connect
+        } else if error.is_timeout() {
+            TransportErrorKind::Timeout
+        } else if error.is_request() || error.is_body() {
+            TransportErrorKind::Request
+        } else {
+            TransportErrorKind::Other
+        };
+        TransportError::new(kind, format!("{}", error))
+    }
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> ReqwestTransport {
+        ReqwestTransport { client }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, TransportError> {
+        let mut builder = self.client.request(request.method, &request.url).headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status().as_u16();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+
+        Ok(HttpResponse { status, headers, body })
+    }
+}
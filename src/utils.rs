@@ -1,7 +1,15 @@
 use crate::errors::{ApiError};
 use uuid::Uuid;
 
-static BASE_URL: &'static str = "https://developers.sarufi.io";
+pub(crate) const BASE_URL: &str = "https://developers.sarufi.io";
+
+/// Environment variable holding the api key
+#[cfg(feature = "env-config")]
+pub(crate) const API_KEY_ENV: &str = "SARUFI_API_KEY";
+
+/// Environment variable overriding the base url
+#[cfg(feature = "env-config")]
+pub(crate) const BASE_URL_ENV: &str = "SARUFI_BASE_URL";
 
 /// Checks to ensure keys are not empty
 pub(crate) fn validate_keys(api_key: &str) -> Result<(), ApiError> {
@@ -12,8 +20,9 @@ pub(crate) fn validate_keys(api_key: &str) -> Result<(), ApiError> {
   Ok(())
 }
 
-pub(crate) fn api_url(path: &str) -> String {
-  format!("{}{}", BASE_URL, path)
+/// Joins an endpoint path onto the configured base url
+pub(crate) fn api_url(base_url: &str, path: &str) -> String {
+  format!("{}{}", base_url, path)
 }
 
 
@@ -23,3 +32,28 @@ pub(crate) fn generate_uuid() -> String {
   uuid.to_string()
 }
 
+
+/// A random value in `[0, 1)`, good enough for spreading retries apart
+pub(crate) fn random_fraction() -> f64 {
+  use std::collections::hash_map::RandomState;
+  use std::hash::{BuildHasher, Hasher};
+
+  let mut hasher = RandomState::new().build_hasher();
+  hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+  (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Converts days since the unix epoch to a `(year, month, day)` date
+#[cfg(any(test, feature = "mock", feature = "fs-import"))]
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let day_of_era = z.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+  (year, month, day)
+}
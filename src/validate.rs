@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+#[cfg(feature = "fs-import")]
+use std::path::Path;
+
+use serde::Serialize;
+
+#[cfg(feature = "fs-import")]
+use crate::errors::ApiError;
+use crate::flow::{FlowState, END_STATE};
+use crate::spec::BotSpec;
+
+/// How bad a [`Diagnostic`] is, only errors make a definition unusable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A problem found in a bot definition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the check, e.g. `unknown-state`
+    pub code: &'static str,
+    /// JSON path of the offending value, e.g. `$.flow.greetings.next_state`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}[{}] {}: {}", severity, self.code, self.path, self.message)
+    }
+}
+
+/// Whether any of the diagnostics is an error
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// Checks a bot definition without calling the api.
+///
+/// Reports transitions to missing states, intents without a flow, states no intent
+/// can reach, empty intents, unknown webhook trigger intents and a few softer smells.
+/// Diagnostics are ordered by the location they point to.
+pub fn validate(spec: &BotSpec) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut push = |severity, code, path: String, message: String| {
+        diagnostics.push(Diagnostic { severity, code, path, message })
+    };
+
+    if spec.name.trim().is_empty() {
+        push(Severity::Error, "empty-name", "$.name".to_owned(), "the bot needs a name".to_owned());
+    }
+
+    if let Some(threshold) = spec.confidence_threshold {
+        if !(0.0..=1.0).contains(&threshold) {
+            push(
+                Severity::Error,
+                "invalid-threshold",
+                "$.confidence_threshold".to_owned(),
+                format!("confidence threshold {} is outside 0..=1", threshold),
+            );
+        }
+    }
+
+    let empty_intents = HashMap::new();
+    let intents = spec.intents.as_ref().unwrap_or(&empty_intents);
+    let states: BTreeMap<&str, &FlowState> = spec
+        .flow
+        .as_ref()
+        .map(|flow| flow.iter().map(|(name, state)| (name.as_str(), state)).collect())
+        .unwrap_or_default();
+    let intent_names: BTreeMap<&str, &Vec<String>> = intents.iter().map(|(k, v)| (k.as_str(), v)).collect();
+
+    let mut seen_examples: HashMap<String, &str> = HashMap::new();
+    for (&name, examples) in &intent_names {
+        let path = format!("$.intents{}", key(name));
+
+        if examples.iter().all(|e| e.trim().is_empty()) {
+            push(Severity::Error, "empty-intent", path.clone(), format!("intent {:?} has no example utterances", name));
+        }
+
+        if !states.contains_key(name) {
+            push(Severity::Error, "intent-without-flow", path.clone(), format!("intent {:?} has no flow state with the same name", name));
+        }
+
+        for (i, example) in examples.iter().enumerate() {
+            let normalized = example.trim().to_lowercase();
+            if normalized.is_empty() {
+                continue;
+            }
+            match seen_examples.get(&normalized) {
+                Some(other) if *other != name => push(
+                    Severity::Warning,
+                    "duplicate-example",
+                    format!("{}[{}]", path, i),
+                    format!("example {:?} is also used by intent {:?}", example, other),
+                ),
+                _ => {
+                    seen_examples.insert(normalized, name);
+                }
+            }
+        }
+    }
+
+    for (&name, state) in &states {
+        let path = format!("$.flow{}", key(name));
+
+        if let Some(next_state) = &state.next_state {
+            if next_state != END_STATE && !states.contains_key(next_state.as_str()) {
+                push(
+                    Severity::Error,
+                    "unknown-state",
+                    format!("{}.next_state", path),
+                    format!("next_state {:?} does not exist", next_state),
+                );
+            }
+        }
+
+        let mut choices: Vec<(&String, &String)> = state.choices.iter().collect();
+        choices.sort();
+        for (option, target) in choices {
+            if target != END_STATE && !states.contains_key(target.as_str()) {
+                push(
+                    Severity::Error,
+                    "unknown-state",
+                    format!("{}{}", path, key(option)),
+                    format!("choice {:?} leads to missing state {:?}", option, target),
+                );
+            }
+        }
+
+        if state.is_choice() && state.fallback_message.is_empty() {
+            push(
+                Severity::Warning,
+                "missing-fallback",
+                path.clone(),
+                "choice state has no fallback_message for unexpected answers".to_owned(),
+            );
+        }
+
+        if state.next_state.is_none() && !state.is_choice() {
+            push(Severity::Warning, "dead-end", path.clone(), "state has neither next_state nor choices".to_owned());
+        }
+
+        if state.message.is_empty() && !state.is_choice() {
+            push(Severity::Info, "empty-message", path, "state sends no message".to_owned());
+        }
+    }
+
+    let reachable = reachable_states(&intent_names, &states);
+    for &name in states.keys() {
+        if !reachable.contains(name) {
+            push(
+                Severity::Warning,
+                "unreachable-state",
+                format!("$.flow{}", key(name)),
+                format!("state {:?} cannot be reached from any intent", name),
+            );
+        }
+    }
+
+    if let Some(triggers) = &spec.webhook_trigger_intents {
+        for (i, intent) in triggers.iter().enumerate() {
+            if !intents.contains_key(intent) {
+                push(
+                    Severity::Error,
+                    "unknown-trigger-intent",
+                    format!("$.webhook_trigger_intents[{}]", i),
+                    format!("webhook trigger intent {:?} is not a known intent", intent),
+                );
+            }
+        }
+
+        // `Option::is_none_or` needs Rust 1.82
+        #[allow(clippy::unnecessary_map_or)]
+        let no_url = spec.webhook_url.as_deref().map_or(true, |url| url.trim().is_empty());
+        if !triggers.is_empty() && no_url {
+            push(
+                Severity::Warning,
+                "missing-webhook-url",
+                "$.webhook_url".to_owned(),
+                "webhook trigger intents are set but there is no webhook_url".to_owned(),
+            );
+        }
+    }
+
+    diagnostics.sort_by(|a, b| a.path.cmp(&b.path).then(b.severity.cmp(&a.severity)));
+    diagnostics
+}
+
+/// Reads a JSON bot definition such as `src/data/test.json` and validates it
+#[cfg(feature = "fs-import")]
+pub fn validate_file<P: AsRef<Path>>(path: P) -> Result<Vec<Diagnostic>, ApiError> {
+    Ok(validate(&BotSpec::from_file(path)?))
+}
+
+/// States reachable by following transitions from the intent entry points
+fn reachable_states<'a>(intents: &BTreeMap<&'a str, &Vec<String>>, states: &BTreeMap<&'a str, &'a FlowState>) -> HashSet<&'a str> {
+    let mut reachable = HashSet::new();
+    let mut queue: VecDeque<&str> = intents.keys().copied().filter(|name| states.contains_key(name)).collect();
+
+    while let Some(name) = queue.pop_front() {
+        if !reachable.insert(name) {
+            continue;
+        }
+        if let Some(state) = states.get(name) {
+            for target in state.transitions() {
+                if let Some((&known, _)) = states.get_key_value(target) {
+                    queue.push_back(known);
+                }
+            }
+        }
+    }
+
+    reachable
+}
+
+/// JSON path segment for an object key
+fn key(name: &str) -> String {
+    let simple = !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if simple {
+        format!(".{}", name)
+    } else {
+        format!("[{}]", serde_json::Value::from(name))
+    }
+}
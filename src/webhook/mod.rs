@@ -0,0 +1,391 @@
+//! Receiver for the webhook calls Sarufi makes when a conversation hits an intent.
+//!
+//! A [`Webhook`] maps intent names to async handlers. Each callback is parsed into
+//! a [`WebhookPayload`], handed to the handler registered for its intent and the
+//! [`WebhookReply`] it returns is sent back to Sarufi as JSON. The handler can be
+//! served on its own with [`Webhook::bind`] or [`Webhook::serve`], or mounted in an
+//! existing application through [`Webhook::handle`] or [`Webhook::handle_request`].
+//!
+//! ```no_run
+//! use sarufi::webhook::{Webhook, WebhookPayload, WebhookReply};
+//!
+//! # async fn run() -> Result<(), sarufi::ApiError> {
+//! let webhook = Webhook::new().on("order_pizza", |payload: WebhookPayload| async move {
+//!     let size = payload.memory_str("pizza_size").unwrap_or("medium").to_owned();
+//!     Ok(WebhookReply::text(format!("One {} pizza coming for chat {}", size, payload.chat_id)))
+//! });
+//!
+//! webhook.serve(([0, 0, 0, 0], 8080).into()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Larger backends register [`IntentHandler`]s on a [`Router`], which hands them a
+//! client and checks that every webhook trigger intent of the bot is covered.
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use hyper::body::HttpBody;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::oneshot;
+
+use crate::conversation::Channel;
+use crate::errors::ApiError;
+use crate::flow::MessageBlock;
+
+mod router;
+
+pub use router::{IntentContext, IntentHandler, Router};
+
+/// Future returned by a webhook handler
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Result<WebhookReply, ApiError>> + Send>>;
+
+type Handler = Arc<dyn Fn(WebhookPayload) -> HandlerFuture + Send + Sync>;
+
+/// A callback sent by Sarufi.
+///
+/// Every field is optional on the wire, fields not modeled here are kept in `extra`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// Intent the message was classified as, selects the handler
+    #[serde(default)]
+    pub intent: String,
+    /// Chat the message belongs to, numeric ids are turned into text
+    #[serde(default, deserialize_with = "text_or_number")]
+    pub chat_id: String,
+    #[serde(default)]
+    pub bot_id: Option<usize>,
+    /// Message that triggered the call, usually a string
+    #[serde(default)]
+    pub message: Value,
+    #[serde(default)]
+    pub message_type: Option<String>,
+    #[serde(default)]
+    pub channel: Option<Channel>,
+    #[serde(default)]
+    pub current_state: Option<String>,
+    #[serde(default)]
+    pub next_state: Option<String>,
+    #[serde(default)]
+    pub confidence: Option<f64>,
+    /// Values collected by the flow so far, keyed by state name
+    #[serde(default)]
+    pub memory: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Answer of a handler, serialized back to Sarufi.
+///
+/// ```
+/// use sarufi::webhook::WebhookReply;
+///
+/// let reply = WebhookReply::text("Your order is confirmed").next_state("end").memory("order_id", 42);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct WebhookReply {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub message: Vec<MessageBlock>,
+    /// State the conversation moves to, the flow decides when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_state: Option<String>,
+    /// Values to store in the memory of the chat
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub memory: Map<String, Value>,
+    /// WhatsApp actions in their wire format, e.g. `{"send_reply_button": {...}}`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Value>,
+}
+
+/// Largest callback body a [`Webhook`] reads unless told otherwise, 1 MiB
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Intent handlers answering Sarufi webhook calls.
+///
+/// Cloning is cheap, handlers are shared.
+#[derive(Clone)]
+pub struct Webhook {
+    handlers: HashMap<String, Handler>,
+    fallback: Option<Handler>,
+    max_body_size: usize,
+}
+
+/// A [`Webhook`] served in the background, it stops when dropped
+pub struct WebhookServer {
+    local_addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl WebhookPayload {
+    /// The message when it is text
+    pub fn text(&self) -> Option<&str> {
+        self.message.as_str()
+    }
+
+    /// A memory value when it is text
+    pub fn memory_str(&self, key: &str) -> Option<&str> {
+        self.memory.get(key).and_then(Value::as_str)
+    }
+}
+
+impl WebhookReply {
+    /// An empty reply, Sarufi carries on with the flow
+    pub fn new() -> WebhookReply {
+        WebhookReply::default()
+    }
+
+    /// A reply made of a single text message
+    pub fn text<S: Into<String>>(text: S) -> WebhookReply {
+        WebhookReply::new().message(text)
+    }
+
+    /// Appends a text message
+    pub fn message<S: Into<String>>(mut self, text: S) -> WebhookReply {
+        self.message.push(MessageBlock::Text(text.into()));
+        self
+    }
+
+    pub fn next_state<S: Into<String>>(mut self, next_state: S) -> WebhookReply {
+        self.next_state = Some(next_state.into());
+        self
+    }
+
+    /// Stores a value in the memory of the chat
+    pub fn memory<S: Into<String>, V: Into<Value>>(mut self, key: S, value: V) -> WebhookReply {
+        self.memory.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn action(mut self, action: Value) -> WebhookReply {
+        self.actions.push(action);
+        self
+    }
+}
+
+impl Default for Webhook {
+    fn default() -> Webhook {
+        Webhook { handlers: HashMap::new(), fallback: None, max_body_size: DEFAULT_MAX_BODY_SIZE }
+    }
+}
+
+impl Webhook {
+    pub fn new() -> Webhook {
+        Webhook::default()
+    }
+
+    /// Largest request body [`Webhook::handle_request`] accepts, bigger ones get a 413
+    pub fn max_body_size(mut self, bytes: usize) -> Webhook {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// Registers the handler of an intent, replacing any previous one
+    pub fn on<S, F, Fut>(mut self, intent: S, handler: F) -> Webhook
+    where
+        S: Into<String>,
+        F: Fn(WebhookPayload) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<WebhookReply, ApiError>> + Send + 'static,
+    {
+        self.handlers.insert(intent.into(), boxed(handler));
+        self
+    }
+
+    /// Handles the intents without a handler of their own
+    pub fn fallback<F, Fut>(mut self, handler: F) -> Webhook
+    where
+        F: Fn(WebhookPayload) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<WebhookReply, ApiError>> + Send + 'static,
+    {
+        self.fallback = Some(boxed(handler));
+        self
+    }
+
+    /// Intents with a handler, sorted
+    pub fn intents(&self) -> Vec<&str> {
+        let mut intents: Vec<&str> = self.handlers.keys().map(String::as_str).collect();
+        intents.sort_unstable();
+        intents
+    }
+
+    /// Whether a call for `intent` reaches a handler, the fallback included
+    pub fn handles(&self, intent: &str) -> bool {
+        self.handlers.contains_key(intent) || self.fallback.is_some()
+    }
+
+    /// Runs the handler of the payload intent
+    pub async fn dispatch(&self, payload: WebhookPayload) -> Result<WebhookReply, ApiError> {
+        let handler = self
+            .handlers
+            .get(&payload.intent)
+            .or(self.fallback.as_ref())
+            .ok_or_else(|| ApiError::GenericError(format!("no webhook handler for intent {:?}", payload.intent)))?;
+        handler(payload).await
+    }
+
+    /// Answers a raw callback body with a status code and a JSON body.
+    ///
+    /// Malformed payloads get a 400, intents without a handler a 404 and failing
+    /// handlers a 500, errors are reported as `{"detail": ...}`.
+    pub async fn handle(&self, body: &[u8]) -> (u16, Value) {
+        let payload: WebhookPayload = match serde_json::from_slice(body) {
+            Ok(payload) => payload,
+            Err(e) => return (400, json!({ "detail": format!("invalid webhook payload: {}", e) })),
+        };
+
+        if !self.handles(&payload.intent) {
+            return (404, json!({ "detail": format!("no handler for intent {:?}", payload.intent) }));
+        }
+
+        let intent = payload.intent.clone();
+        match self.dispatch(payload).await {
+            Ok(reply) => match serde_json::to_value(reply) {
+                Ok(value) => (200, value),
+                Err(e) => (500, json!({ "detail": e.to_string() })),
+            },
+            Err(e) => {
+                log::error!("webhook handler for intent {:?} failed: {}", intent, e);
+                (500, json!({ "detail": e.to_string() }))
+            }
+        }
+    }
+
+    /// Answers a hyper request, only `POST` is accepted and the path is ignored.
+    ///
+    /// Bodies larger than [`Webhook::max_body_size`] are refused with a 413 without
+    /// being read to the end.
+    pub async fn handle_request(&self, request: Request<Body>) -> Response<Body> {
+        if request.method() != Method::POST {
+            return reply(405, json!({ "detail": "Method Not Allowed" }));
+        }
+
+        let length = request.headers().get(CONTENT_LENGTH).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<u64>().ok());
+        if length.is_some_and(|length| length > self.max_body_size as u64) {
+            return too_large(self.max_body_size);
+        }
+
+        let mut body = request.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(chunk) if bytes.len() + chunk.len() > self.max_body_size => return too_large(self.max_body_size),
+                Ok(chunk) => bytes.extend_from_slice(&chunk),
+                Err(e) => return reply(400, json!({ "detail": e.to_string() })),
+            }
+        }
+
+        let (status, body) = self.handle(&bytes).await;
+        reply(status, body)
+    }
+
+    /// Serves the webhook in the background, bind port 0 to get a free port
+    pub async fn bind(self, addr: SocketAddr) -> Result<WebhookServer, ApiError> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let signal = async {
+                signal.await.ok();
+            };
+            if let Err(e) = self.run(listener, signal).await {
+                log::error!("webhook server failed: {}", e);
+            }
+        });
+
+        Ok(WebhookServer { local_addr, shutdown: Some(shutdown) })
+    }
+
+    /// Serves the webhook until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), ApiError> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        self.run(listener, std::future::pending()).await
+    }
+
+    async fn run<F: Future<Output = ()>>(self, listener: std::net::TcpListener, signal: F) -> Result<(), ApiError> {
+        let webhook = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let webhook = webhook.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let webhook = webhook.clone();
+                    async move { Ok::<_, Infallible>(webhook.handle_request(request).await) }
+                }))
+            }
+        });
+
+        Server::from_tcp(listener)
+            .map_err(|e| ApiError::GenericError(format!("{}", e)))?
+            .serve(make_service)
+            .with_graceful_shutdown(signal)
+            .await
+            .map_err(|e| ApiError::GenericError(format!("{}", e)))
+    }
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("intents", &self.intents())
+            .field("fallback", &self.fallback.is_some())
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+impl WebhookServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Address to configure as the webhook url of the bot
+    pub fn url(&self) -> String {
+        format!("http://{}", self.local_addr)
+    }
+}
+
+impl Drop for WebhookServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+fn boxed<F, Fut>(handler: F) -> Handler
+where
+    F: Fn(WebhookPayload) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<WebhookReply, ApiError>> + Send + 'static,
+{
+    Arc::new(move |payload| Box::pin(handler(payload)) as HandlerFuture)
+}
+
+fn reply(status: u16, body: Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+fn too_large(limit: usize) -> Response<Body> {
+    reply(413, json!({ "detail": format!("webhook payload larger than {} bytes", limit) }))
+}
+
+/// Chat ids are strings on most channels and numbers on some
+fn text_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(text) => text,
+        Value::Null => String::new(),
+        other => other.to_string(),
+    })
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use super::{Webhook, WebhookPayload, WebhookReply};
+use crate::bot::Bot;
+use crate::errors::ApiError;
+use crate::Sarufi;
+
+/// Business logic run when a conversation hits an intent.
+///
+/// Closures taking an [`IntentContext`] and returning a future implement it, types
+/// with state of their own implement it with [`async_trait`](crate::async_trait).
+///
+/// ```
+/// use sarufi::webhook::{IntentContext, IntentHandler, WebhookReply};
+/// use sarufi::{async_trait, ApiError};
+///
+/// struct CheckBalance {
+///     currency: String,
+/// }
+///
+/// #[async_trait]
+/// impl IntentHandler for CheckBalance {
+///     async fn handle(&self, context: IntentContext) -> Result<WebhookReply, ApiError> {
+///         let account = context.slot_str("account_number").unwrap_or_default();
+///         Ok(WebhookReply::text(format!("Account {} holds 1,000 {}", account, self.currency)))
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait IntentHandler: Send + Sync {
+    async fn handle(&self, context: IntentContext) -> Result<WebhookReply, ApiError>;
+}
+
+#[async_trait]
+impl<F, Fut> IntentHandler for F
+where
+    F: Fn(IntentContext) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<WebhookReply, ApiError>> + Send,
+{
+    async fn handle(&self, context: IntentContext) -> Result<WebhookReply, ApiError> {
+        self(context).await
+    }
+}
+
+/// The call an [`IntentHandler`] answers, with a client to act on the conversation
+#[derive(Clone)]
+pub struct IntentContext {
+    payload: WebhookPayload,
+    bot_id: Option<usize>,
+    client: Sarufi,
+}
+
+/// Maps intent names to [`IntentHandler`]s.
+///
+/// ```no_run
+/// use sarufi::webhook::{IntentContext, Router, WebhookReply};
+///
+/// # async fn run(api: sarufi::Sarufi) -> Result<(), sarufi::ApiError> {
+/// let bot = api.get_bot(1145).await?;
+/// let router = Router::new(api.clone()).route("book_table", |context: IntentContext| async move {
+///     context.set_state("confirm_booking").await?;
+///     Ok(WebhookReply::new())
+/// });
+///
+/// // fails when a webhook trigger intent of the bot has no handler
+/// router.verify(&bot)?;
+/// router.into_webhook().serve(([0, 0, 0, 0], 8080).into()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Router {
+    client: Sarufi,
+    bot_id: Option<usize>,
+    handlers: HashMap<String, Arc<dyn IntentHandler>>,
+    fallback: Option<Arc<dyn IntentHandler>>,
+}
+
+impl IntentContext {
+    pub fn intent(&self) -> &str {
+        &self.payload.intent
+    }
+
+    pub fn chat_id(&self) -> &str {
+        &self.payload.chat_id
+    }
+
+    /// Bot of the call, from the payload or the one configured on the [`Router`]
+    pub fn bot_id(&self) -> Option<usize> {
+        self.bot_id
+    }
+
+    /// Values collected by the flow so far, keyed by state name
+    pub fn memory(&self) -> &Map<String, Value> {
+        &self.payload.memory
+    }
+
+    /// A value collected by the flow
+    pub fn slot(&self, name: &str) -> Option<&Value> {
+        self.payload.memory.get(name)
+    }
+
+    /// A value collected by the flow when it is text
+    pub fn slot_str(&self, name: &str) -> Option<&str> {
+        self.payload.memory_str(name)
+    }
+
+    /// The raw callback
+    pub fn payload(&self) -> &WebhookPayload {
+        &self.payload
+    }
+
+    pub fn client(&self) -> &Sarufi {
+        &self.client
+    }
+
+    /// Moves the chat to another flow state
+    pub async fn set_state(&self, next_state: &str) -> Result<String, ApiError> {
+        let bot_id = self
+            .bot_id
+            .ok_or_else(|| ApiError::GenericError(format!("the call for intent {:?} carries no bot id", self.payload.intent)))?;
+        self.client.update_conversation_state(bot_id, &self.payload.chat_id, next_state).await
+    }
+}
+
+impl Router {
+    pub fn new(client: Sarufi) -> Router {
+        Router { client, bot_id: None, handlers: HashMap::new(), fallback: None }
+    }
+
+    /// Bot assumed when a call does not name one
+    pub fn bot_id(mut self, bot_id: usize) -> Router {
+        self.bot_id = Some(bot_id);
+        self
+    }
+
+    /// Registers the handler of an intent, replacing any previous one
+    pub fn route<S: Into<String>, H: IntentHandler + 'static>(mut self, intent: S, handler: H) -> Router {
+        self.handlers.insert(intent.into(), Arc::new(handler));
+        self
+    }
+
+    /// Handles the intents without a handler of their own
+    pub fn fallback<H: IntentHandler + 'static>(mut self, handler: H) -> Router {
+        self.fallback = Some(Arc::new(handler));
+        self
+    }
+
+    /// Intents with a handler, sorted
+    pub fn intents(&self) -> Vec<&str> {
+        let mut intents: Vec<&str> = self.handlers.keys().map(String::as_str).collect();
+        intents.sort_unstable();
+        intents
+    }
+
+    /// Webhook trigger intents of `bot` without a handler, the fallback does not count
+    pub fn missing<'a>(&self, bot: &'a Bot) -> Vec<&'a str> {
+        bot.webhook_trigger_intents.iter().map(String::as_str).filter(|intent| !self.handlers.contains_key(*intent)).collect()
+    }
+
+    /// Fails when a webhook trigger intent of `bot` has no handler, meant to run at startup
+    pub fn verify(&self, bot: &Bot) -> Result<(), ApiError> {
+        let missing = self.missing(bot);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(ApiError::GenericError(format!("bot {} has no handler for the webhook trigger intents {}", bot.id, missing.join(", "))))
+    }
+
+    /// Runs the handler of the payload intent
+    pub async fn dispatch(&self, payload: WebhookPayload) -> Result<WebhookReply, ApiError> {
+        let handler = self
+            .handlers
+            .get(&payload.intent)
+            .or(self.fallback.as_ref())
+            .ok_or_else(|| ApiError::GenericError(format!("no handler for intent {:?}", payload.intent)))?;
+
+        let bot_id = payload.bot_id.or(self.bot_id);
+        handler.handle(IntentContext { payload, bot_id, client: self.client.clone() }).await
+    }
+
+    /// A [`Webhook`] answering the calls with this router
+    pub fn into_webhook(self) -> Webhook {
+        let router = Arc::new(self);
+        let mut webhook = Webhook::new();
+
+        for intent in router.handlers.keys() {
+            let router = router.clone();
+            webhook = webhook.on(intent.clone(), move |payload| {
+                let router = router.clone();
+                async move { router.dispatch(payload).await }
+            });
+        }
+        if router.fallback.is_some() {
+            let router = router.clone();
+            webhook = webhook.fallback(move |payload| {
+                let router = router.clone();
+                async move { router.dispatch(payload).await }
+            });
+        }
+        webhook
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("base_url", &self.client.base_url())
+            .field("bot_id", &self.bot_id)
+            .field("intents", &self.intents())
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
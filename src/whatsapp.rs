@@ -0,0 +1,165 @@
+//! Typed payloads returned by the WhatsApp conversation endpoint.
+//!
+//! WhatsApp replies come as a list of `actions`, each an object with a single
+//! `send_*` key mirroring the WhatsApp Cloud API interactive messages.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::conversation::{Media, MediaKind};
+
+/// One step of a WhatsApp reply
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Action {
+    /// `send_message`: plain text messages
+    SendMessage(Vec<String>),
+    /// `send_reply_button`: up to three quick reply buttons
+    SendReplyButton(ReplyButtons),
+    /// `send_button`: a list message with sections of rows
+    SendButton(ListMessage),
+    /// `send_images`, `send_videos`, ...
+    SendMedia { kind: MediaKind, items: Vec<Media> },
+    /// Anything this version of the SDK does not know about
+    Other(Value),
+}
+
+/// Interactive message with reply buttons
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyButtons {
+    pub header: Option<String>,
+    pub body: String,
+    pub footer: Option<String>,
+    pub buttons: Vec<Button>,
+}
+
+/// A quick reply button, `id` is what comes back when the user taps it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Button {
+    pub id: String,
+    pub title: String,
+}
+
+/// Interactive list message opened by a single button
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListMessage {
+    pub header: Option<String>,
+    pub body: String,
+    pub footer: Option<String>,
+    /// Label of the button opening the list
+    pub button: String,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Section {
+    pub title: Option<String>,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Row {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+}
+
+impl Action {
+    /// Parses a single action object, unknown or malformed actions end up as [`Action::Other`]
+    pub fn parse(value: &Value) -> Action {
+        parse_action(value).unwrap_or_else(|| Action::Other(value.clone()))
+    }
+
+    /// Parses the `actions` list of a reply
+    pub fn parse_all(value: &Value) -> Vec<Action> {
+        value.as_array().map(|items| items.iter().map(Action::parse).collect()).unwrap_or_default()
+    }
+}
+
+fn parse_action(value: &Value) -> Option<Action> {
+    let object = value.as_object()?;
+    if object.len() != 1 {
+        return None;
+    }
+
+    let (key, payload) = object.iter().next()?;
+    match key.as_str() {
+        "send_message" => Some(Action::SendMessage(strings(payload)?)),
+        "send_reply_button" => parse_reply_buttons(payload).map(Action::SendReplyButton),
+        "send_button" => parse_list(payload).map(Action::SendButton),
+        other => {
+            let kind = MediaKind::from_key(other)?;
+            let items = match payload {
+                Value::Array(_) => serde_json::from_value(payload.clone()).ok()?,
+                single => vec![serde_json::from_value(single.clone()).ok()?],
+            };
+            Some(Action::SendMedia { kind, items })
+        }
+    }
+}
+
+fn strings(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::String(text) => Some(vec![text.clone()]),
+        Value::Array(items) => items.iter().map(|item| item.as_str().map(str::to_owned)).collect(),
+        _ => None,
+    }
+}
+
+/// Texts are either plain strings or `{"text": ...}` objects
+fn text(value: &Value) -> Option<String> {
+    value.as_str().or_else(|| value["text"].as_str()).map(str::to_owned)
+}
+
+fn parse_reply_buttons(payload: &Value) -> Option<ReplyButtons> {
+    let buttons = payload["action"]["buttons"]
+        .as_array()?
+        .iter()
+        .map(|button| {
+            let reply = if button["reply"].is_object() { &button["reply"] } else { button };
+            Some(Button { id: text_or_number(&reply["id"])?, title: text(&reply["title"])? })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(ReplyButtons {
+        header: text(&payload["header"]),
+        body: text(&payload["body"])?,
+        footer: text(&payload["footer"]),
+        buttons,
+    })
+}
+
+fn parse_list(payload: &Value) -> Option<ListMessage> {
+    let action = &payload["action"];
+    let sections = action["sections"]
+        .as_array()?
+        .iter()
+        .map(|section| {
+            let rows = section["rows"]
+                .as_array()?
+                .iter()
+                .map(|row| {
+                    Some(Row {
+                        id: text_or_number(&row["id"])?,
+                        title: text(&row["title"])?,
+                        description: text(&row["description"]),
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(Section { title: text(&section["title"]), rows })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(ListMessage {
+        header: text(&payload["header"]),
+        body: text(&payload["body"])?,
+        footer: text(&payload["footer"]),
+        button: text(&action["button"])?,
+        sections,
+    })
+}
+
+fn text_or_number(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(number) => Some(number.to_string()),
+        other => text(other),
+    }
+}
@@ -0,0 +1,103 @@
+//! Runs the `sarufi` binary on local files without an api key, and against the mock server.
+#![cfg(feature = "cli")]
+
+use std::process::{Command, Output};
+
+fn sarufi(args: &[&str]) -> Output {
+    // outside the repository so no `.env` is picked up
+    Command::new(env!("CARGO_BIN_EXE_sarufi"))
+        .args(args)
+        .current_dir(std::env::temp_dir())
+        .env_remove("SARUFI_API_KEY")
+        .env_remove("SARUFI_BASE_URL")
+        .output()
+        .unwrap()
+}
+
+/// Runs against a local [`MockServer`](sarufi::mock::MockServer)
+#[cfg(feature = "mock")]
+fn sarufi_at(server: &sarufi::mock::MockServer, args: &[&str]) -> Output {
+    let mut args = args.to_vec();
+    args.extend(["--api-key", "secret", "--base-url", server.base_url()]);
+    sarufi(&args)
+}
+
+fn fixture(path: &str) -> String {
+    format!("{}/src/data/{}", env!("CARGO_MANIFEST_DIR"), path)
+}
+
+#[test]
+fn test_graph_of_local_definition() {
+    let output = sarufi(&["bots", "graph", &fixture("pizza_project")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let dot = String::from_utf8(output.stdout).unwrap();
+    assert!(dot.starts_with("digraph \"Pizza bot\" {"));
+    assert!(dot.contains("\"state:order_pizza\" -> \"state:small_pizza\" [label=\"1\"];"));
+
+    let output = sarufi(&["bots", "graph", &fixture("test.json"), "--format", "mermaid"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("flowchart LR"));
+
+    // commands calling the api still ask for the key
+    let output = sarufi(&["bots", "graph", "42"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_diff_of_local_definitions() {
+    let output = sarufi(&["bots", "diff", &fixture("test.json"), &fixture("test.json")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "no changes");
+
+    let output = sarufi(&["bots", "diff", &fixture("test.json"), &fixture("pizza_project"), "--unified"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let patch = String::from_utf8(output.stdout).unwrap();
+    assert!(patch.starts_with("--- a/My Test Bot\n+++ b/Pizza bot\n"), "{}", patch);
+}
+
+// the binary blocks its test thread, the mock server runs on the other worker
+#[cfg(feature = "mock")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_bot_commands_against_mock() {
+    let server = sarufi::mock::MockServer::start().await.unwrap();
+
+    let output = sarufi_at(&server, &["bots", "list"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let table = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with("ID") && lines[0].contains("NAME"), "{}", table);
+    assert!(lines[1].starts_with("1045") && lines[2].starts_with("1122"), "{}", table);
+
+    let output = sarufi_at(&server, &["bots", "get", "1045", "-o", "json"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let bot: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(bot["id"], 1045);
+    assert_eq!(bot["name"], "My Rusty Chatbot");
+
+    let output = sarufi_at(&server, &["bots", "create", "--file", &fixture("test.json"), "-o", "json"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let created: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(created["name"], "My Test Bot");
+    let id = created["id"].as_u64().unwrap() as usize;
+    assert_eq!(server.bot(id).unwrap()["webhook_url"], "https://example.com/webhook");
+
+    let output = sarufi_at(&server, &["chat", &id.to_string(), "hello", "--chat-id", "chat-1"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("chat id: chat-1"));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Hello"));
+    assert_eq!(server.chat_state(id, "chat-1").as_deref(), Some("end"));
+}
+
+#[cfg(feature = "mock")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_missing_api_key() {
+    let server = sarufi::mock::MockServer::start().await.unwrap();
+    for args in [&["bots", "list"][..], &["bots", "get", "1045"], &["bots", "create", "--file", &fixture("test.json")], &["chat", "1045", "hello"]] {
+        let mut args = args.to_vec();
+        args.extend(["--base-url", server.base_url()]);
+        let output = sarufi(&args);
+        assert!(!output.status.success(), "{:?} succeeded", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("error: Invalid api_key"), "{:?}", args);
+    }
+    assert!(server.requests().is_empty());
+}